@@ -1,3 +1,5 @@
+#[cfg(feature = "executable")]
+pub mod executor;
 #[cfg(feature = "async_oai")]
 pub mod async_openai;
 
@@ -5,3 +7,5 @@ pub mod async_openai;
 pub(crate) mod send;
 #[cfg(feature = "retry")]
 pub mod retry;
+#[cfg(feature = "rate_limit")]
+pub mod rate_limit;
\ No newline at end of file
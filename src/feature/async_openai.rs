@@ -1,8 +1 @@
-pub mod prompt_result;
-pub mod executable;
-pub mod executable_flow;
-
-use async_openai::config::Config;
-use serde::Deserialize;
-use crate::prelude::Context;
-
+pub mod backend;
@@ -0,0 +1,231 @@
+use async_openai::Client;
+use async_openai::config::Config;
+use async_openai::error::OpenAIError;
+use async_openai::types::{
+    ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessage, ChatCompletionRequestAssistantMessageArgs,
+    ChatCompletionRequestDeveloperMessage, ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
+    ChatCompletionRequestToolMessageArgs, ChatCompletionRequestUserMessage, ChatCompletionTool, ChatCompletionToolType,
+    CreateChatCompletionRequest, CreateChatCompletionRequestArgs, CreateChatCompletionResponse, FunctionCall, FunctionObject,
+    CompletionUsage, ResponseFormatJsonSchema, Stop,
+};
+#[cfg(feature = "stream")]
+use async_openai::types::{ChatCompletionStreamOptions, CreateChatCompletionStreamResponse};
+#[cfg(feature = "stream")]
+use futures::StreamExt;
+#[cfg(feature = "stream")]
+use crate::feature::executor::stream::{ChatDelta, ChatDeltaStream, ToolCallDelta};
+use async_openai::types::FinishReason as OpenAIFinishReason;
+use async_openai::types::ResponseFormat as OpenAIResponseFormat;
+use crate::feature::executor::wait_hint;
+use crate::feature::executor::backend::{ApiError, BackendError, BackendFuture, ChatChoice, ChatMessage, ChatRequest, ChatResponse, FinishReason, LlmBackend, ResponseFormat, Role, TokenUsage, ToolCall, ToolDefinition};
+
+impl<Cfg> LlmBackend for Client<Cfg>
+where
+    Cfg: Config
+{
+    fn chat(&self, request: ChatRequest) -> BackendFuture<'_, ChatResponse> {
+        Box::pin(async move {
+            let request = to_openai_request(request)?;
+            let response = self.chat()
+                .create(request)
+                .await?;
+            Ok(from_openai_response(response))
+        })
+    }
+    #[cfg(feature = "stream")]
+    fn chat_stream(&self, request: ChatRequest) -> BackendFuture<'_, ChatDeltaStream<'_>> {
+        Box::pin(async move {
+            let mut request = to_openai_request(request)?;
+            request.stream_options = Some(ChatCompletionStreamOptions { include_usage: true });
+            let chunks = self.chat()
+                .create_stream(request)
+                .await?;
+            let deltas: ChatDeltaStream<'_> = Box::pin(chunks.map(|chunk| Ok(from_openai_chunk(chunk?))));
+            Ok(deltas)
+        })
+    }
+}
+
+fn to_openai_message(message: ChatMessage) -> Result<ChatCompletionRequestMessage, OpenAIError> {
+    let ChatMessage { role, content, tool_calls, tool_call_id } = message;
+    Ok(match role {
+        Role::System => ChatCompletionRequestSystemMessage::from(content).into(),
+        Role::Developer => ChatCompletionRequestDeveloperMessage::from(content).into(),
+        Role::User => ChatCompletionRequestUserMessage::from(content).into(),
+        Role::Assistant if tool_calls.is_empty() => ChatCompletionRequestAssistantMessage::from(content).into(),
+        Role::Assistant => {
+            let mut message = ChatCompletionRequestAssistantMessageArgs::default();
+            if !content.is_empty() {
+                message.content(content);
+            }
+            message.tool_calls(tool_calls.into_iter().map(to_openai_tool_call).collect::<Vec<_>>())
+                .build()?
+                .into()
+        },
+        Role::Tool => ChatCompletionRequestToolMessageArgs::default()
+            .content(content)
+            .tool_call_id(tool_call_id.unwrap_or_default())
+            .build()?
+            .into(),
+    })
+}
+
+fn to_openai_tool_call(call: ToolCall) -> ChatCompletionMessageToolCall {
+    ChatCompletionMessageToolCall {
+        id: call.id,
+        r#type: ChatCompletionToolType::Function,
+        function: FunctionCall {
+            name: call.name,
+            arguments: call.arguments,
+        },
+    }
+}
+
+fn to_openai_tool(tool: ToolDefinition) -> ChatCompletionTool {
+    ChatCompletionTool {
+        r#type: ChatCompletionToolType::Function,
+        function: FunctionObject {
+            name: tool.name,
+            description: tool.description,
+            parameters: Some(tool.parameters),
+            strict: None,
+        },
+    }
+}
+
+fn to_openai_request(request: ChatRequest) -> Result<CreateChatCompletionRequest, OpenAIError> {
+    let messages = request.messages
+        .into_iter()
+        .map(to_openai_message)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut openai_request = CreateChatCompletionRequestArgs::default()
+        .messages(messages)
+        .model(request.model)
+        .build()?;
+    let params = request.params;
+    openai_request.temperature = params.temperature;
+    openai_request.top_p = params.top_p;
+    openai_request.max_completion_tokens = params.max_tokens;
+    openai_request.stop = params.stop.map(Stop::StringArray);
+    openai_request.seed = params.seed;
+    openai_request.response_format = request.response_format.map(to_openai_response_format);
+    if !request.tools.is_empty() {
+        openai_request.tools = Some(request.tools.into_iter().map(to_openai_tool).collect());
+    }
+    Ok(openai_request)
+}
+
+fn to_openai_response_format(format: ResponseFormat) -> OpenAIResponseFormat {
+    match format {
+        ResponseFormat::Text => OpenAIResponseFormat::Text,
+        ResponseFormat::JsonObject => OpenAIResponseFormat::JsonObject,
+        ResponseFormat::JsonSchema { name, description, schema, strict } => OpenAIResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                description,
+                name,
+                schema: Some(schema),
+                strict,
+            },
+        },
+    }
+}
+
+fn from_openai_response(response: CreateChatCompletionResponse) -> ChatResponse {
+    ChatResponse {
+        id: response.id,
+        model: response.model,
+        choices: response.choices
+            .into_iter()
+            .map(|choice| ChatChoice {
+                index: choice.index,
+                content: choice.message.content,
+                refusal: choice.message.refusal,
+                tool_calls: choice.message.tool_calls
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|call| ToolCall {
+                        id: call.id,
+                        name: call.function.name,
+                        arguments: call.function.arguments,
+                    })
+                    .collect(),
+                finish_reason: choice.finish_reason.map(from_openai_finish_reason),
+            })
+            .collect(),
+        usage: response.usage.map(from_openai_usage),
+    }
+}
+
+//Only the first choice is streamed, the usage arrives in a last chunk without choices
+#[cfg(feature = "stream")]
+fn from_openai_chunk(chunk: CreateChatCompletionStreamResponse) -> ChatDelta {
+    let choice = chunk.choices.into_iter().find(|choice| choice.index == 0);
+    let (delta, finish_reason) = match choice {
+        Some(choice) => (Some(choice.delta), choice.finish_reason),
+        None => (None, None),
+    };
+    let (content, refusal, tool_calls) = match delta {
+        Some(delta) => (delta.content, delta.refusal, delta.tool_calls.unwrap_or_default()),
+        None => (None, None, Vec::new()),
+    };
+    ChatDelta {
+        id: Some(chunk.id),
+        model: Some(chunk.model),
+        content,
+        refusal,
+        tool_calls: tool_calls.into_iter()
+            .map(|call| {
+                let (name, arguments) = match call.function {
+                    Some(function) => (function.name, function.arguments.unwrap_or_default()),
+                    None => (None, String::new()),
+                };
+                ToolCallDelta {
+                    index: call.index,
+                    id: call.id,
+                    name,
+                    arguments,
+                }
+            })
+            .collect(),
+        finish_reason: finish_reason.map(from_openai_finish_reason),
+        usage: chunk.usage.map(from_openai_usage),
+    }
+}
+
+fn from_openai_finish_reason(reason: OpenAIFinishReason) -> FinishReason {
+    match reason {
+        OpenAIFinishReason::Stop => FinishReason::Stop,
+        OpenAIFinishReason::Length => FinishReason::Length,
+        OpenAIFinishReason::ToolCalls => FinishReason::ToolCalls,
+        OpenAIFinishReason::ContentFilter => FinishReason::ContentFilter,
+        OpenAIFinishReason::FunctionCall => FinishReason::Other("function_call".to_string()),
+    }
+}
+
+fn from_openai_usage(usage: CompletionUsage) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        total_tokens: usage.total_tokens,
+    }
+}
+
+impl From<OpenAIError> for BackendError {
+    fn from(value: OpenAIError) -> Self {
+        match value {
+            //async-openai drops the response headers, the wait hint can only come from the message
+            OpenAIError::ApiError(e) => BackendError::Api(ApiError {
+                status: None,
+                code: e.code,
+                kind: e.r#type,
+                retry_after: wait_hint::from_message(&e.message),
+                message: e.message,
+            }),
+            OpenAIError::Reqwest(e) => BackendError::Transport(Box::new(e)),
+            OpenAIError::JSONDeserialize(e) => BackendError::InvalidResponse(e.to_string()),
+            OpenAIError::StreamError(e) => BackendError::Stream(e),
+            OpenAIError::InvalidArgument(e) => BackendError::InvalidRequest(e),
+            e => BackendError::Other(Box::new(e)),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+pub mod backend;
+pub mod model;
+pub mod conversation;
+pub mod prompt_result;
+pub mod executable;
+pub mod executable_flow;
+pub mod structured;
+pub mod processor;
+pub mod tool;
+pub mod tokenizer;
+pub mod usage;
+pub(crate) mod wait_hint;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "batch")]
+pub mod batch;
+#[cfg(feature = "timeout")]
+pub mod interrupt;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
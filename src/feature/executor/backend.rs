@@ -0,0 +1,348 @@
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+#[cfg(feature = "stream")]
+use crate::feature::executor::stream::{ChatDelta, ChatDeltaStream};
+use crate::feature::executor::wait_hint;
+pub use crate::prompt::role::Role;
+
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, BackendError>> + Send + 'a>>;
+
+//Anything able to answer a chat request: an API client, a local engine, a mock...
+pub trait LlmBackend: Send + Sync {
+    fn chat(&self, request: ChatRequest) -> BackendFuture<'_, ChatResponse>;
+    //Backends without native streaming deliver the whole response as one delta
+    #[cfg(feature = "stream")]
+    fn chat_stream(&self, request: ChatRequest) -> BackendFuture<'_, ChatDeltaStream<'_>> {
+        Box::pin(async move {
+            let response = self.chat(request).await?;
+            let deltas: ChatDeltaStream<'_> = Box::pin(futures::stream::once(async move {
+                Ok(ChatDelta::from_response(response))
+            }));
+            Ok(deltas)
+        })
+    }
+}
+impl<B> LlmBackend for Arc<B>
+where
+    B: LlmBackend + ?Sized
+{
+    fn chat(&self, request: ChatRequest) -> BackendFuture<'_, ChatResponse> {
+        (**self).chat(request)
+    }
+    #[cfg(feature = "stream")]
+    fn chat_stream(&self, request: ChatRequest) -> BackendFuture<'_, ChatDeltaStream<'_>> {
+        (**self).chat_stream(request)
+    }
+}
+impl<B> LlmBackend for Box<B>
+where
+    B: LlmBackend + ?Sized
+{
+    fn chat(&self, request: ChatRequest) -> BackendFuture<'_, ChatResponse> {
+        (**self).chat(request)
+    }
+    #[cfg(feature = "stream")]
+    fn chat_stream(&self, request: ChatRequest) -> BackendFuture<'_, ChatDeltaStream<'_>> {
+        (**self).chat_stream(request)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String, //JSON, as produced by the model
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>, //assistant messages asking for tools
+    pub tool_call_id: Option<String>, //tool messages answering one of those calls
+}
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        ChatMessage {
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+    pub fn developer(content: impl Into<String>) -> Self {
+        Self::new(Role::Developer, content)
+    }
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+    pub fn tool(call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        ChatMessage {
+            tool_call_id: Some(call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+    pub fn with_tool_calls(mut self, tool_calls: Vec<ToolCall>) -> Self {
+        self.tool_calls = tool_calls;
+        self
+    }
+}
+
+//Sampling knobs of a request, unset fields are left to the provider
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+}
+impl GenerationParams {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+    pub fn stop<I, T>(mut self, stop: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>
+    {
+        self.stop = Some(stop.into_iter().map(Into::into).collect());
+        self
+    }
+    pub fn seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    //Fields set here win, the others are taken from `defaults`
+    pub fn with_defaults(&self, defaults: &GenerationParams) -> GenerationParams {
+        GenerationParams {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            max_tokens: self.max_tokens.or(defaults.max_tokens),
+            stop: self.stop.clone().or_else(|| defaults.stop.clone()),
+            seed: self.seed.or(defaults.seed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema {
+        name: String,
+        description: Option<String>,
+        schema: serde_json::Value,
+        strict: Option<bool>,
+    },
+}
+impl ResponseFormat {
+    pub fn json_schema(name: impl Into<String>, schema: serde_json::Value) -> Self {
+        ResponseFormat::JsonSchema {
+            name: name.into(),
+            description: None,
+            schema,
+            strict: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub params: GenerationParams,
+    pub response_format: Option<ResponseFormat>,
+    pub tools: Vec<ToolDefinition>,
+}
+impl ChatRequest {
+    pub fn new(model: impl Into<String>, messages: Vec<ChatMessage>) -> Self {
+        ChatRequest {
+            model: model.into(),
+            messages,
+            params: GenerationParams::default(),
+            response_format: None,
+            tools: Vec::new(),
+        }
+    }
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
+    }
+    pub fn with_response_format(mut self, response_format: Option<ResponseFormat>) -> Self {
+        self.response_format = response_format;
+        self
+    }
+    pub fn with_tools(mut self, tools: Vec<ToolDefinition>) -> Self {
+        self.tools = tools;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishReason {
+    Stop,
+    Length,
+    ToolCalls,
+    ContentFilter,
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatChoice {
+    pub index: u32,
+    pub content: Option<String>,
+    pub refusal: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<FinishReason>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatResponse {
+    pub id: String,
+    pub model: String,
+    pub choices: Vec<ChatChoice>,
+    pub usage: Option<TokenUsage>,
+}
+impl ChatResponse {
+    pub fn first_choice(&self) -> Option<&ChatChoice> {
+        self.choices.first()
+    }
+    pub fn first_content(&self) -> Option<&str> {
+        self.first_choice().and_then(|choice| choice.content.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: Option<u16>,
+    pub code: Option<String>,
+    pub kind: Option<String>,
+    pub message: String,
+    pub retry_after: Option<Duration>, //how long the server asked to wait before trying again
+}
+impl ApiError {
+    pub fn new(message: impl Into<String>) -> Self {
+        ApiError {
+            status: None,
+            code: None,
+            kind: None,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+    pub fn with_kind(mut self, kind: impl Into<String>) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+    //Takes the wait hint from `retry-after(-ms)` or `x-ratelimit-reset-*` response headers, if any
+    pub fn with_headers<'h, I>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (&'h str, &'h str)>
+    {
+        if let Some(retry_after) = wait_hint::from_headers(headers) {
+            self.retry_after = Some(retry_after);
+        }
+        self
+    }
+    pub fn is_context_length_exceeded(&self) -> bool {
+        self.code.as_deref() == Some("context_length_exceeded")
+            || self.message.contains("maximum context length")
+    }
+    //Providers disagree on where the status lives, some only put it in `code`
+    pub fn matches_status(&self, status: u16) -> bool {
+        self.status == Some(status)
+            || self.code.as_ref().is_some_and(|code| *code == status.to_string())
+    }
+}
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(kind) = &self.kind {
+            write!(f, "{kind}: ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(status) = self.status {
+            write!(f, " (status: {status})")?;
+        }
+        if let Some(code) = &self.code {
+            write!(f, " (code: {code})")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BackendError {
+    #[error("api error: {0}")]
+    Api(ApiError),
+    #[error("transport error: {0}")]
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("stream error: {0}")]
+    Stream(String),
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    #[cfg(feature = "retry")]
+    #[error("circuit open for model {model}, retry in {retry_after:?}")]
+    CircuitOpen {
+        model: String,
+        retry_after: Duration,
+    },
+    #[error("{0}")]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+impl From<ApiError> for BackendError {
+    fn from(value: ApiError) -> Self {
+        BackendError::Api(value)
+    }
+}
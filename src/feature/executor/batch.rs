@@ -0,0 +1,142 @@
+use std::borrow::BorrowMut;
+use std::marker::PhantomData;
+use futures::StreamExt;
+use crate::feature::executor::backend::{ChatResponse, LlmBackend};
+use crate::feature::executor::executable::{execute_prompt, ExecutableSettings, ExecutionScope};
+use crate::feature::executor::prompt_result::{ProcessorError, PromptExecutableError};
+#[cfg(feature = "retry")]
+use crate::feature::executor::executable::execute_prompt_with_retry;
+#[cfg(feature = "retry")]
+use crate::feature::retry::RetryPolicy;
+#[cfg(feature = "timeout")]
+use crate::feature::executor::interrupt::{CancellationToken, Interrupts};
+use crate::feature::executor::usage::{StepUsage, UsageRecorder};
+use crate::prelude::{Context, Prompt};
+
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 8;
+
+type ProgressCallback<'e> = Box<dyn FnMut(&BatchProgress) + Send + 'e>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchProgress {
+    pub completed: usize,
+    pub failed: usize,
+    pub total: Option<usize>, //known when the contexts came with an exact length
+}
+
+//One input of the batch, with its context handed back
+pub struct BatchItem<X, S> {
+    pub index: usize,
+    pub context: X,
+    pub result: Result<Option<S>, PromptExecutableError>,
+    pub usage: StepUsage,
+}
+
+//Runs one executable over many contexts, owned or `&mut`, a failing item never stops the others
+pub struct BatchExecution<'e, C, S, P, F, I>
+where
+    P: ?Sized,
+    F: ?Sized
+{
+    prompt: &'e P,
+    processor: &'e F,
+    settings: &'e ExecutableSettings<'e, C>,
+    contexts: I,
+    concurrency: usize,
+    on_progress: Option<ProgressCallback<'e>>,
+    #[cfg(feature = "retry")]
+    policy: Option<RetryPolicy>,
+    #[cfg(feature = "timeout")]
+    cancel: Option<CancellationToken>,
+    result: PhantomData<fn() -> S>,
+}
+impl<'e, C, S, P, F, I, X> BatchExecution<'e, C, S, P, F, I>
+where
+    C: Context,
+    P: Prompt<C> + ?Sized,
+    F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + ?Sized,
+    I: IntoIterator<Item = X>,
+    X: BorrowMut<C>
+{
+    pub(crate) fn new(prompt: &'e P, processor: &'e F, settings: &'e ExecutableSettings<'e, C>, contexts: I) -> Self {
+        BatchExecution {
+            prompt,
+            processor,
+            settings,
+            contexts,
+            concurrency: DEFAULT_BATCH_CONCURRENCY,
+            on_progress: None,
+            #[cfg(feature = "retry")]
+            policy: None,
+            #[cfg(feature = "timeout")]
+            cancel: None,
+            result: PhantomData,
+        }
+    }
+    //How many items may be executing at once
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+    //Called each time an item is done, in completion order
+    pub fn on_progress(mut self, on_progress: impl FnMut(&BatchProgress) + Send + 'e) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+    #[cfg(feature = "retry")]
+    pub fn retry(mut self, policy: impl Into<RetryPolicy>) -> Self {
+        self.policy = Some(policy.into());
+        self
+    }
+    //Items not done when the token is cancelled come back as `PromptExecutableError::Cancelled`
+    #[cfg(feature = "timeout")]
+    pub fn cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+    //Items come back in input order
+    pub async fn execute(self, backend: &dyn LlmBackend) -> Vec<BatchItem<X, S>> {
+        let BatchExecution { prompt, processor, settings, contexts, concurrency, mut on_progress, .. } = self;
+        #[cfg(feature = "retry")]
+        let policy = self.policy.as_ref();
+        #[cfg(feature = "timeout")]
+        let cancel = self.cancel.as_ref();
+        let contexts = contexts.into_iter();
+        let total = match contexts.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        };
+        let mut progress = BatchProgress { completed: 0, failed: 0, total };
+        let mut items = futures::stream::iter(contexts.enumerate())
+            .map(|(index, mut context)| async move {
+                let usage = UsageRecorder::default();
+                let scope = ExecutionScope {
+                    usage: Some(&usage),
+                    #[cfg(feature = "timeout")]
+                    interrupts: Interrupts { cancel, deadline: None },
+                    ..Default::default()
+                };
+                #[cfg(feature = "retry")]
+                if let Some(policy) = policy {
+                    let result = execute_prompt_with_retry(prompt, processor, settings, context.borrow_mut(), backend, scope, policy).await;
+                    return BatchItem { index, context, result, usage: usage.finish() };
+                }
+                let result = execute_prompt(prompt, processor, settings, context.borrow_mut(), backend, scope).await;
+                BatchItem { index, context, result, usage: usage.finish() }
+            })
+            .buffer_unordered(concurrency)
+            .inspect(|item| {
+                progress.completed += 1;
+                if item.result.is_err() {
+                    progress.failed += 1;
+                }
+                if let Some(on_progress) = &mut on_progress {
+                    on_progress(&progress);
+                }
+            })
+            .collect::<Vec<_>>()
+            .await;
+        items.sort_by_key(|item| item.index);
+        items
+    }
+}
@@ -0,0 +1,100 @@
+use crate::feature::executor::backend::{ChatMessage, Role};
+
+//How much of the recorded history is sent along with a new prompt.
+//System messages are never windowed out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistoryWindow {
+    #[default]
+    Unbounded,
+    LastMessages(usize),
+    LastTurns(usize), //a turn starts at a user message
+}
+
+//How a single step takes part in the conversation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistoryMode {
+    #[default]
+    Include,
+    ReadOnly,
+    RecordOnly,
+    Exclude,
+}
+impl HistoryMode {
+    pub fn reads(self) -> bool {
+        matches!(self, HistoryMode::Include | HistoryMode::ReadOnly)
+    }
+    pub fn records(self) -> bool {
+        matches!(self, HistoryMode::Include | HistoryMode::RecordOnly)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Conversation {
+    messages: Vec<ChatMessage>,
+    window: HistoryWindow,
+}
+impl Conversation {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn with_system(mut self, content: impl Into<String>) -> Self {
+        self.messages.push(ChatMessage::system(content));
+        self
+    }
+    pub fn with_window(mut self, window: HistoryWindow) -> Self {
+        self.window = window;
+        self
+    }
+    pub fn set_window(&mut self, window: HistoryWindow) {
+        self.window = window;
+    }
+    pub fn window(&self) -> HistoryWindow {
+        self.window
+    }
+    pub fn push(&mut self, message: ChatMessage) {
+        self.messages.push(message);
+    }
+    pub fn messages(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+    //The full transcript is kept, the window only applies to what is sent
+    pub fn history(&self) -> Vec<ChatMessage> {
+        let windowed = self.messages.iter()
+            .enumerate()
+            .filter(|(_, message)| message.role != Role::System)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let first_kept = match self.window {
+            HistoryWindow::Unbounded => 0,
+            HistoryWindow::LastMessages(count) => windowed.len().saturating_sub(count),
+            HistoryWindow::LastTurns(count) => {
+                let turn_starts = windowed.iter()
+                    .enumerate()
+                    .filter(|&(_, &index)| self.messages[index].role == Role::User)
+                    .map(|(position, _)| position)
+                    .collect::<Vec<_>>();
+                match count {
+                    0 => windowed.len(),
+                    _ => turn_starts.len()
+                        .checked_sub(count)
+                        .map_or(0, |turn| turn_starts[turn])
+                }
+            }
+        };
+        let first_kept = windowed.get(first_kept).copied().unwrap_or(self.messages.len());
+        self.messages.iter()
+            .enumerate()
+            .filter(|&(index, message)| message.role == Role::System || index >= first_kept)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+}
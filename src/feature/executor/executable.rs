@@ -0,0 +1,781 @@
+use serde::Deserialize;
+#[cfg(feature = "schema")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use crate::feature::executor::backend::{ChatMessage, ChatRequest, ChatResponse, GenerationParams, LlmBackend, ResponseFormat, Role};
+use crate::feature::executor::model::{ModelList, ModelNeeds};
+use crate::feature::executor::tool::{Tool, DEFAULT_MAX_TOOL_ITERATIONS};
+use crate::feature::executor::tokenizer::{render, TokenBudget};
+#[cfg(feature = "stream")]
+use futures::StreamExt;
+#[cfg(feature = "stream")]
+use crate::feature::executor::backend::BackendError;
+#[cfg(feature = "stream")]
+use crate::feature::executor::stream::{DeltaSink, PromptStream, ResponseAssembler};
+use crate::feature::executor::conversation::{Conversation, HistoryMode};
+use crate::feature::executor::prompt_result::PromptExecutableError::{self, InvalidModelSelection, ModelNotSet, ToolIterationLimit};
+use crate::feature::executor::prompt_result::{ProcessorError, PromptResult};
+#[cfg(feature = "schema")]
+use crate::feature::executor::structured::structured_output;
+#[cfg(feature = "send")]
+use crate::feature::send::control::SendPromptVariant;
+#[cfg(feature = "send")]
+use crate::feature::send::result::SendPromptResult;
+#[cfg(feature = "retry")]
+use crate::feature::retry::{retrier::Retrier, RetryPolicy};
+#[cfg(feature = "batch")]
+use std::borrow::BorrowMut;
+#[cfg(feature = "batch")]
+use crate::feature::executor::batch::BatchExecution;
+#[cfg(feature = "timeout")]
+use std::time::Duration;
+#[cfg(feature = "timeout")]
+use crate::feature::executor::interrupt::{within, Interrupts};
+use crate::feature::executor::usage::{CallUsage, UsageRecorder};
+use crate::prelude::{Context, Prompt, PromptVariant};
+use std::collections::HashMap;
+use std::time::Instant;
+
+pub type Processor<'a, C, S> = dyn Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + 'a;
+pub type SendProcessor<'a, C, S> = dyn Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + Send + Sync + 'a;
+
+//Everything an executable carries besides its prompt and processor
+pub(crate) struct ExecutableSettings<'a, C> {
+    pub(crate) models: ModelList<'a>,
+    pub(crate) history: HistoryMode,
+    pub(crate) params: GenerationParams,
+    pub(crate) response_format: Option<ResponseFormat>,
+    pub(crate) tools: Vec<Tool<'a, C>>,
+    pub(crate) max_tool_iterations: usize,
+    pub(crate) budget: Option<TokenBudget>,
+    #[cfg(feature = "timeout")]
+    pub(crate) timeout: Option<Duration>,
+}
+impl<'a, C> ExecutableSettings<'a, C> {
+    pub(crate) fn with_models(models: ModelList<'a>, response_format: Option<ResponseFormat>) -> Self {
+        ExecutableSettings {
+            models,
+            history: HistoryMode::default(),
+            params: GenerationParams::default(),
+            response_format,
+            tools: Vec::new(),
+            max_tool_iterations: DEFAULT_MAX_TOOL_ITERATIONS,
+            budget: None,
+            #[cfg(feature = "timeout")]
+            timeout: None,
+        }
+    }
+
+    fn needs(&self) -> ModelNeeds {
+        ModelNeeds::of(self.response_format.as_ref(), !self.tools.is_empty())
+    }
+    pub(crate) fn check_tokens<P>(&self, prompt: &P, context: &C, select_model: Option<usize>) -> Result<usize, PromptExecutableError>
+    where
+        C: Context,
+        P: Prompt<C> + ?Sized
+    {
+        let budget = self.budget.clone().unwrap_or_default();
+        let index = select_model.unwrap_or(0);
+        let spec = match self.models.get(index) {
+            Some(spec) => spec,
+            None if self.models.is_empty() => return Err(ModelNotSet),
+            None => return Err(InvalidModelSelection(index)),
+        };
+        let tokens = budget.get_tokenizer().count_messages(&render(prompt, context, &HashMap::new())?);
+        match spec.context_window.map(|window| budget.limit(window, self.params.max_tokens)) {
+            Some(limit) if tokens > limit => Err(PromptExecutableError::ContextWindowExceeded {
+                model: spec.name.to_string(),
+                tokens,
+                limit,
+            }),
+            _ => Ok(tokens),
+        }
+    }
+    //Models a retry may rotate through
+    #[cfg(feature = "retry")]
+    pub(crate) fn capable_models(&self) -> Vec<usize> {
+        self.models.capable(&self.needs())
+    }
+}
+
+//Getters and builder setters over `settings`, shared by every executable with models,
+//expanded inside impls that have `'a` and `C` in scope and a prompt at `self.prompt.prompt`
+macro_rules! settings_methods {
+    () => {
+        pub fn model_count(&self) -> usize {
+            self.settings.models.len()
+        }
+        pub fn models(&self) -> &$crate::feature::executor::model::ModelList<'a> {
+            &self.settings.models
+        }
+        pub fn history(mut self, mode: $crate::feature::executor::conversation::HistoryMode) -> Self {
+            self.settings.history = mode;
+            self
+        }
+        pub fn get_params(&self) -> &$crate::feature::executor::backend::GenerationParams {
+            &self.settings.params
+        }
+        pub fn params(mut self, params: $crate::feature::executor::backend::GenerationParams) -> Self {
+            self.settings.params = params;
+            self
+        }
+        pub fn temperature(mut self, temperature: f32) -> Self {
+            self.settings.params = self.settings.params.temperature(temperature);
+            self
+        }
+        pub fn top_p(mut self, top_p: f32) -> Self {
+            self.settings.params = self.settings.params.top_p(top_p);
+            self
+        }
+        pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+            self.settings.params = self.settings.params.max_tokens(max_tokens);
+            self
+        }
+        pub fn stop<I, T>(mut self, stop: I) -> Self
+        where
+            I: IntoIterator<Item = T>,
+            T: Into<String>
+        {
+            self.settings.params = self.settings.params.stop(stop);
+            self
+        }
+        pub fn seed(mut self, seed: i64) -> Self {
+            self.settings.params = self.settings.params.seed(seed);
+            self
+        }
+        pub fn get_response_format(&self) -> Option<&$crate::feature::executor::backend::ResponseFormat> {
+            self.settings.response_format.as_ref()
+        }
+        pub fn response_format(mut self, response_format: $crate::feature::executor::backend::ResponseFormat) -> Self {
+            self.settings.response_format = Some(response_format);
+            self
+        }
+        pub fn tool(mut self, tool: $crate::feature::executor::tool::Tool<'a, C>) -> Self {
+            self.settings.tools.push(tool);
+            self
+        }
+        pub fn max_tool_iterations(mut self, max_tool_iterations: usize) -> Self {
+            self.settings.max_tool_iterations = max_tool_iterations;
+            self
+        }
+        pub fn get_token_budget(&self) -> Option<&$crate::feature::executor::tokenizer::TokenBudget> {
+            self.settings.budget.as_ref()
+        }
+        //Requests are checked against the model's context window and cut as the budget allows before being sent
+        pub fn token_budget(mut self, budget: $crate::feature::executor::tokenizer::TokenBudget) -> Self {
+            self.settings.budget = Some(budget);
+            self
+        }
+        //Tokens the step renders to, history left aside, failing when it can't fit the selected model
+        pub fn check_tokens(&self, context: &C, select_model: Option<usize>) -> Result<usize, $crate::feature::executor::prompt_result::PromptExecutableError> {
+            self.settings.check_tokens(self.prompt.prompt, context, select_model)
+        }
+        #[cfg(feature = "timeout")]
+        pub fn get_timeout(&self) -> Option<std::time::Duration> {
+            self.settings.timeout
+        }
+        //Bounds each attempt, tool calls included, a retry gets a fresh one
+        #[cfg(feature = "timeout")]
+        pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+            self.settings.timeout = Some(timeout);
+            self
+        }
+    };
+}
+#[cfg(all(feature = "retry", feature = "send"))]
+pub(crate) use settings_methods;
+
+//What the caller brings to a single execution
+#[derive(Default)]
+pub(crate) struct ExecutionScope<'c> {
+    pub(crate) select_model: Option<usize>,
+    pub(crate) conversation: Option<&'c mut Conversation>,
+    pub(crate) defaults: Option<&'c GenerationParams>,
+    pub(crate) usage: Option<&'c UsageRecorder>,
+    #[cfg(feature = "stream")]
+    pub(crate) sink: Option<&'c DeltaSink>,
+    #[cfg(feature = "timeout")]
+    pub(crate) interrupts: Interrupts<'c>,
+}
+
+pub struct PromptExecutable<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a>
+{
+    prompt: &'a PromptVariant<'a, C>,
+    processor: Box<Processor<'a, C, S>>,
+    response_format: Option<ResponseFormat>,
+}
+#[cfg(feature = "send")]
+pub struct SendPromptExecutable<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a> + Send + Sync + 'a
+{
+    prompt: &'a SendPromptVariant<'a, C>,
+    processor: Box<Processor<'a, C, S>>,
+    response_format: Option<ResponseFormat>,
+}
+impl<'a, C> PromptVariant<'a, C>
+where
+    C: Context
+{
+    pub fn to_executable<S, F>(&'a self, processor: F) -> PromptExecutable<'a, C, S>
+    where
+        S: Deserialize<'a>,
+        F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + 'a
+    {
+        PromptExecutable {
+            prompt: self,
+            processor: Box::new(processor),
+            response_format: None,
+        }
+    }
+    //The schema of S is sent along and the reply is deserialized into it
+    #[cfg(feature = "schema")]
+    pub fn to_structured_executable<S>(&'a self) -> PromptExecutable<'a, C, S>
+    where
+        S: DeserializeOwned + JsonSchema + 'a
+    {
+        PromptExecutable {
+            prompt: self,
+            processor: Box::new(structured_output::<C, S>),
+            response_format: Some(ResponseFormat::json_schema_for::<S>()),
+        }
+    }
+}
+#[cfg(feature = "send")]
+impl<'a, C> SendPromptVariant<'a, C>
+where
+    C: Context
+{
+    pub fn to_executable<S, F>(&'a self, processor: F) -> SendPromptExecutable<'a, C, S>
+    where
+        S: Deserialize<'a> + Send + Sync + 'a,
+        F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + Send + Sync + 'a
+    {
+        SendPromptExecutable {
+            prompt: self,
+            processor: Box::new(processor),
+            response_format: None,
+        }
+    }
+    //The schema of S is sent along and the reply is deserialized into it
+    #[cfg(feature = "schema")]
+    pub fn to_structured_executable<S>(&'a self) -> SendPromptExecutable<'a, C, S>
+    where
+        S: DeserializeOwned + JsonSchema + 'a + Send + Sync
+    {
+        SendPromptExecutable {
+            prompt: self,
+            processor: Box::new(structured_output::<C, S>),
+            response_format: Some(ResponseFormat::json_schema_for::<S>()),
+        }
+    }
+}
+pub struct PromptExecutableWithModel<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a>
+{
+    prompt: PromptExecutable<'a, C, S>,
+    settings: Box<ExecutableSettings<'a, C>>, //NOTE: boxed to keep the executable variants small
+}
+#[cfg(feature = "send")]
+pub struct SendPromptExecutableWithModel<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a> + Send + Sync + 'a
+{
+    prompt: SendPromptExecutable<'a, C, S>,
+    settings: Box<ExecutableSettings<'a, C>>,
+}
+impl<'a, C, S> PromptExecutable<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a>
+{
+    pub fn get_processor(&self) -> &Processor<'_, C, S> {
+        &self.processor
+    }
+    pub fn models(mut self, models: impl Into<ModelList<'a>>) -> PromptExecutableWithModel<'a, C, S> {
+        PromptExecutableWithModel {
+            settings: Box::new(ExecutableSettings::with_models(models.into(), self.response_format.take())),
+            prompt: self,
+        }
+    }
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> SendPromptExecutable<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a> + Send + Sync + 'a
+{
+    pub fn get_processor(&self) -> &Processor<'_, C, S> {
+        &self.processor
+    }
+    pub fn models(mut self, models: impl Into<ModelList<'a>>) -> SendPromptExecutableWithModel<'a, C, S> {
+        SendPromptExecutableWithModel {
+            settings: Box::new(ExecutableSettings::with_models(models.into(), self.response_format.take())),
+            prompt: self,
+        }
+    }
+}
+impl<'a, C, S> PromptExecutableWithModel<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a>
+{
+    pub fn inner_variant(&self) -> &PromptVariant<'a, C> {
+        self.prompt.prompt
+    }
+    settings_methods!();
+    pub async fn execute(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>) -> PromptResult<'a, S> {
+        self.run(context, backend, ExecutionScope {
+            select_model,
+            ..Default::default()
+        }).await
+    }
+    pub async fn execute_in(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>, conversation: &mut Conversation) -> PromptResult<'a, S> {
+        self.run(context, backend, ExecutionScope {
+            select_model,
+            conversation: Some(conversation),
+            ..Default::default()
+        }).await
+    }
+    //Yields content deltas as they arrive, the processed result comes last as `StreamEvent::Done`
+    #[cfg(feature = "stream")]
+    pub fn execute_stream<'s>(&'s self, context: &'s mut C, backend: &'s dyn LlmBackend, select_model: Option<usize>) -> PromptStream<impl Future<Output = Result<Option<S>, PromptExecutableError>> + 's, S> {
+        let (sink, chunks) = DeltaSink::channel();
+        PromptStream::new(async move {
+            execute_prompt(
+                self.prompt.prompt,
+                self.prompt.get_processor(),
+                &self.settings,
+                context,
+                backend,
+                ExecutionScope {
+                    select_model,
+                    sink: Some(&sink),
+                    ..Default::default()
+                }
+            ).await
+        }, chunks)
+    }
+    //Executes once per context, see `BatchExecution` for concurrency, progress and retries
+    #[cfg(feature = "batch")]
+    pub fn batch<I, X>(&self, contexts: I) -> BatchExecution<'_, C, S, PromptVariant<'a, C>, Processor<'a, C, S>, I>
+    where
+        I: IntoIterator<Item = X>,
+        X: BorrowMut<C>
+    {
+        BatchExecution::new(self.prompt.prompt, &*self.prompt.processor, &self.settings, contexts)
+    }
+    //Retries failed attempts as the policy says, a plain number is the default policy with that many retries
+    #[cfg(feature = "retry")]
+    pub async fn execute_with_retry(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>, policy: impl Into<RetryPolicy>) -> PromptResult<'a, S> {
+        self.run_with_retry(context, backend, ExecutionScope {
+            select_model,
+            ..Default::default()
+        }, &policy.into()).await
+    }
+    #[cfg(feature = "retry")]
+    pub async fn execute_in_with_retry(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>, conversation: &mut Conversation, policy: impl Into<RetryPolicy>) -> PromptResult<'a, S> {
+        self.run_with_retry(context, backend, ExecutionScope {
+            select_model,
+            conversation: Some(conversation),
+            ..Default::default()
+        }, &policy.into()).await
+    }
+    #[cfg(feature = "retry")]
+    pub(crate) async fn run_with_retry(&self, context: &mut C, backend: &dyn LlmBackend, scope: ExecutionScope<'_>, policy: &RetryPolicy) -> PromptResult<'a, S> {
+        let usage = UsageRecorder::default();
+        let result = execute_prompt_with_retry(
+            self.prompt.prompt,
+            self.prompt.get_processor(),
+            &self.settings,
+            context,
+            backend,
+            ExecutionScope {
+                usage: Some(&usage),
+                ..scope
+            },
+            policy
+        ).await;
+        let result = match result {
+            Ok(result) => PromptResult::ok(result),
+            Err(e) => PromptResult::err(e),
+        };
+        result.with_usage(usage.finish())
+    }
+    pub(crate) async fn run(&self, context: &mut C, backend: &dyn LlmBackend, scope: ExecutionScope<'_>) -> PromptResult<'a, S> {
+        let usage = UsageRecorder::default();
+        let result = execute_prompt(
+            self.prompt.prompt,
+            self.prompt.get_processor(),
+            &self.settings,
+            context,
+            backend,
+            ExecutionScope {
+                usage: Some(&usage),
+                ..scope
+            }
+        ).await;
+        let result = match result {
+            Ok(result) => PromptResult::ok(result),
+            Err(e) => PromptResult::err(e),
+        };
+        result.with_usage(usage.finish())
+    }
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> SendPromptExecutableWithModel<'a, C, S>
+where
+    C: Context,
+    S: Deserialize<'a> + Send + Sync + 'a
+{
+    pub fn inner_variant(&self) -> &SendPromptVariant<'a, C> {
+        self.prompt.prompt
+    }
+    settings_methods!();
+    pub async fn execute(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>) -> SendPromptResult<'a, S> {
+        self.run(context, backend, ExecutionScope {
+            select_model,
+            ..Default::default()
+        }).await
+    }
+    pub async fn execute_in(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>, conversation: &mut Conversation) -> SendPromptResult<'a, S> {
+        self.run(context, backend, ExecutionScope {
+            select_model,
+            conversation: Some(conversation),
+            ..Default::default()
+        }).await
+    }
+    //Yields content deltas as they arrive, the processed result comes last as `StreamEvent::Done`
+    #[cfg(feature = "stream")]
+    pub fn execute_stream<'s>(&'s self, context: &'s mut C, backend: &'s dyn LlmBackend, select_model: Option<usize>) -> PromptStream<impl Future<Output = Result<Option<S>, PromptExecutableError>> + 's, S> {
+        let (sink, chunks) = DeltaSink::channel();
+        PromptStream::new(async move {
+            execute_prompt(
+                self.prompt.prompt,
+                self.prompt.get_processor(),
+                &self.settings,
+                context,
+                backend,
+                ExecutionScope {
+                    select_model,
+                    sink: Some(&sink),
+                    ..Default::default()
+                }
+            ).await
+        }, chunks)
+    }
+    //Executes once per context, see `BatchExecution` for concurrency, progress and retries
+    #[cfg(feature = "batch")]
+    pub fn batch<I, X>(&self, contexts: I) -> BatchExecution<'_, C, S, SendPromptVariant<'a, C>, Processor<'a, C, S>, I>
+    where
+        I: IntoIterator<Item = X>,
+        X: BorrowMut<C>
+    {
+        BatchExecution::new(self.prompt.prompt, &*self.prompt.processor, &self.settings, contexts)
+    }
+    //Retries failed attempts as the policy says, a plain number is the default policy with that many retries
+    #[cfg(feature = "retry")]
+    pub async fn execute_with_retry(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>, policy: impl Into<RetryPolicy>) -> SendPromptResult<'a, S> {
+        self.run_with_retry(context, backend, ExecutionScope {
+            select_model,
+            ..Default::default()
+        }, &policy.into()).await
+    }
+    #[cfg(feature = "retry")]
+    pub async fn execute_in_with_retry(&self, context: &mut C, backend: &dyn LlmBackend, select_model: Option<usize>, conversation: &mut Conversation, policy: impl Into<RetryPolicy>) -> SendPromptResult<'a, S> {
+        self.run_with_retry(context, backend, ExecutionScope {
+            select_model,
+            conversation: Some(conversation),
+            ..Default::default()
+        }, &policy.into()).await
+    }
+    #[cfg(feature = "retry")]
+    pub(crate) async fn run_with_retry(&self, context: &mut C, backend: &dyn LlmBackend, scope: ExecutionScope<'_>, policy: &RetryPolicy) -> SendPromptResult<'a, S> {
+        let usage = UsageRecorder::default();
+        let result = execute_prompt_with_retry(
+            self.prompt.prompt,
+            self.prompt.get_processor(),
+            &self.settings,
+            context,
+            backend,
+            ExecutionScope {
+                usage: Some(&usage),
+                ..scope
+            },
+            policy
+        ).await;
+        let result = match result {
+            Ok(result) => SendPromptResult::ok(result),
+            Err(e) => SendPromptResult::err(e),
+        };
+        result.with_usage(usage.finish())
+    }
+    pub(crate) async fn run(&self, context: &mut C, backend: &dyn LlmBackend, scope: ExecutionScope<'_>) -> SendPromptResult<'a, S> {
+        let usage = UsageRecorder::default();
+        let result = execute_prompt(
+            self.prompt.prompt,
+            self.prompt.get_processor(),
+            &self.settings,
+            context,
+            backend,
+            ExecutionScope {
+                usage: Some(&usage),
+                ..scope
+            }
+        ).await;
+        let result = match result {
+            Ok(result) => SendPromptResult::ok(result),
+            Err(e) => SendPromptResult::err(e),
+        };
+        result.with_usage(usage.finish())
+    }
+}
+
+//Shared by every executable flavour, they only differ in the result wrapper
+pub(crate) async fn execute_prompt<C, S, P, F>(
+    prompt: &P,
+    processor: &F,
+    settings: &ExecutableSettings<'_, C>,
+    context: &mut C,
+    backend: &dyn LlmBackend,
+    scope: ExecutionScope<'_>
+) -> Result<Option<S>, PromptExecutableError>
+where
+    C: Context,
+    P: Prompt<C> + ?Sized,
+    F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + ?Sized
+{
+    #[cfg(feature = "timeout")]
+    let interrupts = scope.interrupts;
+    let execution = execute_candidates(prompt, processor, settings, context, backend, scope);
+    #[cfg(feature = "timeout")]
+    let execution = interrupts.guard(execution);
+    execution.await
+}
+
+async fn execute_candidates<C, S, P, F>(
+    prompt: &P,
+    processor: &F,
+    settings: &ExecutableSettings<'_, C>,
+    context: &mut C,
+    backend: &dyn LlmBackend,
+    mut scope: ExecutionScope<'_>
+) -> Result<Option<S>, PromptExecutableError>
+where
+    C: Context,
+    P: Prompt<C> + ?Sized,
+    F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + ?Sized
+{
+    let mut candidates = settings.models.candidates(scope.select_model, &settings.needs())?
+        .into_iter()
+        .peekable();
+    while let Some(model) = candidates.next() {
+        let attempt = ExecutionScope {
+            select_model: Some(model),
+            conversation: scope.conversation.as_deref_mut(),
+            defaults: scope.defaults,
+            usage: scope.usage,
+            #[cfg(feature = "stream")]
+            sink: scope.sink,
+            #[cfg(feature = "timeout")]
+            interrupts: Interrupts::default(),
+        };
+        let result = run_prompt(prompt, processor, settings, context, backend, attempt);
+        #[cfg(feature = "timeout")]
+        let result = within(settings.timeout, result);
+        let result = result.await;
+        #[cfg(feature = "stream")]
+        if let (Err(_), Some(sink)) = (&result, scope.sink) {
+            sink.reset();
+        }
+        match result {
+            Err(e) if candidates.peek().is_some() && settings.models.falls_back_on(&e) => continue,
+            result => return result,
+        }
+    }
+    unreachable!() //NOTE: candidates are never empty
+}
+
+//Failed attempts leave no trace in the conversation, only the one that succeeds is recorded
+#[cfg(feature = "retry")]
+pub(crate) async fn execute_prompt_with_retry<C, S, P, F>(
+    prompt: &P,
+    processor: &F,
+    settings: &ExecutableSettings<'_, C>,
+    context: &mut C,
+    backend: &dyn LlmBackend,
+    mut scope: ExecutionScope<'_>,
+    policy: &RetryPolicy
+) -> Result<Option<S>, PromptExecutableError>
+where
+    C: Context,
+    P: Prompt<C> + ?Sized,
+    F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + ?Sized
+{
+    #[cfg(feature = "timeout")]
+    let interrupts = scope.interrupts;
+    //NOTE: interrupts bound the waits between attempts too, so they never reach the retrier
+    let execution = async move {
+        let mut retrier = Retrier::new(policy, settings.capable_models(), scope.select_model);
+        loop {
+            let attempt = ExecutionScope {
+                select_model: retrier.select_model(),
+                conversation: scope.conversation.as_deref_mut(),
+                defaults: scope.defaults,
+                usage: scope.usage,
+                #[cfg(feature = "stream")]
+                sink: scope.sink,
+                #[cfg(feature = "timeout")]
+                interrupts: Interrupts::default(),
+            };
+            match execute_candidates(prompt, processor, settings, context, backend, attempt).await {
+                Ok(result) => return Ok(result),
+                Err(e) => retrier.recover(e).await?,
+            }
+        }
+    };
+    #[cfg(feature = "timeout")]
+    let execution = interrupts.guard(execution);
+    execution.await
+}
+
+async fn run_prompt<C, S, P, F>(
+    prompt: &P,
+    processor: &F,
+    settings: &ExecutableSettings<'_, C>,
+    context: &mut C,
+    backend: &dyn LlmBackend,
+    scope: ExecutionScope<'_>
+) -> Result<Option<S>, PromptExecutableError>
+where
+    C: Context,
+    P: Prompt<C> + ?Sized,
+    F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + ?Sized
+{
+    #[cfg(feature = "stream")]
+    let sink = scope.sink;
+    let ExecutionScope { select_model, conversation, defaults, usage, .. } = scope;
+    let selected_model = select_model.unwrap_or(0);
+    let spec = match settings.models.get(selected_model) {
+        Some(spec) => spec,
+        None if settings.models.is_empty() => return Err(ModelNotSet),
+        None => return Err(InvalidModelSelection(selected_model)),
+    };
+    let mut history = match &conversation {
+        Some(conversation) if settings.history.reads() => conversation.history(),
+        _ => Vec::new(),
+    };
+    let params = match defaults {
+        Some(defaults) => settings.params.with_defaults(defaults),
+        None => settings.params.clone(),
+    };
+    let mut exchange = match (&settings.budget, spec.context_window) {
+        (Some(budget), Some(window)) => budget.fit(prompt, context, &mut history, budget.limit(window, params.max_tokens), spec.name)?,
+        _ => render(prompt, context, &HashMap::new())?,
+    };
+    if exchange.is_empty() {
+        return Ok(None);
+    }
+    let step_len = exchange.len();
+    let tools = settings.tools.iter()
+        .map(|tool| tool.definition().clone())
+        .collect::<Vec<_>>();
+    let mut iterations = 0;
+    let response = loop {
+        let mut messages = history.clone();
+        messages.extend(exchange.iter().cloned());
+        let request = ChatRequest::new(spec.name, messages)
+            .with_params(params.clone())
+            .with_response_format(settings.response_format.clone())
+            .with_tools(tools.clone());
+        let started = Instant::now();
+        #[cfg(feature = "stream")]
+        let response = match sink {
+            Some(sink) => stream_response(backend, request, sink).await,
+            None => backend.chat(request).await,
+        };
+        #[cfg(not(feature = "stream"))]
+        let response = backend.chat(request).await;
+        if let Some(usage) = usage {
+            let tokens = response.as_ref().ok().and_then(|response| response.usage);
+            usage.record(CallUsage {
+                model: spec.name.to_string(),
+                tokens,
+                latency: started.elapsed(),
+                cost: spec.cost.zip(tokens).map(|(cost, tokens)| cost.of(&tokens)),
+            });
+        }
+        let response = response?;
+        let Some(choice) = response.first_choice().filter(|choice| !choice.tool_calls.is_empty() && !tools.is_empty()) else {
+            break response;
+        };
+        if iterations == settings.max_tool_iterations {
+            return Err(ToolIterationLimit(iterations));
+        }
+        iterations += 1;
+        exchange.push(ChatMessage::assistant(choice.content.clone().unwrap_or_default()).with_tool_calls(choice.tool_calls.clone()));
+        for call in &choice.tool_calls {
+            let output = match settings.tools.iter().find(|tool| tool.definition().name == call.name) {
+                Some(tool) => tool.call(&call.arguments, context),
+                None => Tool::<C>::failure(format!("unknown tool `{}`", call.name)),
+            };
+            exchange.push(ChatMessage::tool(&call.id, output));
+        }
+    };
+    let reply = ChatMessage::assistant(response.first_content().unwrap_or_default());
+    let result = processor(response, context)?;
+    if let Some(conversation) = conversation && settings.history.records() {
+        record_exchange(conversation, exchange, step_len, reply);
+    }
+    Ok(result)
+}
+
+//Only the dialogue is carried on: the step's user and assistant messages, the tool exchange and the reply.
+//System and developer messages are instructions for the step alone and are not recorded,
+//unless the step has no user message, then they are recorded as its user turn so the reply answers something.
+//A prefill is recorded as part of the answer it started.
+fn record_exchange(conversation: &mut Conversation, mut exchange: Vec<ChatMessage>, step_len: usize, reply: ChatMessage) {
+    exchange.push(reply);
+    let mut answers = exchange.split_off(step_len).into_iter();
+    let mut step = exchange;
+    let mut answer = answers.next().unwrap(); //NOTE: the reply is always there
+    if step.last().is_some_and(|message| message.role == Role::Assistant) {
+        let prefill = step.pop().unwrap();
+        answer.content = prefill.content + &answer.content;
+    }
+    if step.iter().any(|message| message.role == Role::User) {
+        step.retain(|message| matches!(message.role, Role::User | Role::Assistant));
+    } else if !step.is_empty() {
+        let instructions = step.iter()
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        step = vec![ChatMessage::user(instructions)];
+    }
+    step.into_iter()
+        .chain(std::iter::once(answer))
+        .chain(answers)
+        .for_each(|message| conversation.push(message));
+}
+
+#[cfg(feature = "stream")]
+async fn stream_response(backend: &dyn LlmBackend, request: ChatRequest, sink: &DeltaSink) -> Result<ChatResponse, BackendError> {
+    let mut assembler = ResponseAssembler::new(&request.model);
+    let mut deltas = backend.chat_stream(request).await?;
+    while let Some(delta) = deltas.next().await {
+        let delta = delta?;
+        if let Some(content) = &delta.content {
+            sink.delta(content.clone());
+        }
+        assembler.push(delta);
+    }
+    Ok(assembler.finish())
+}
@@ -0,0 +1,982 @@
+use std::collections::BTreeSet;
+use std::iter::Peekable;
+use serde::Deserialize;
+use crate::feature::executor::backend::{GenerationParams, LlmBackend};
+use crate::feature::executor::conversation::Conversation;
+use crate::feature::executor::executable::{ExecutionScope, PromptExecutableWithModel};
+#[cfg(feature = "send")]
+use crate::feature::executor::executable::{SendPromptExecutableWithModel};
+use crate::feature::executor::prompt_result::PromptResult;
+use crate::feature::executor::usage::ChainUsage;
+#[cfg(feature = "send")]
+use crate::feature::send::result::SendPromptResult;
+#[cfg(feature = "retry")]
+use crate::feature::retry::RetryPolicy;
+#[cfg(feature = "timeout")]
+use std::time::Duration;
+#[cfg(feature = "timeout")]
+use crate::feature::executor::interrupt::{CancellationToken, Deadline, Interrupts};
+use crate::prompt::context::Context;
+use crate::prompt::error::{IfPromptBuilderError, LoopPromptBuilderError, PromptError};
+use crate::prompt::naive::{check_missing, merge_missing, undeclared, Prompt};
+
+#[derive(Default)]
+pub struct ExecutablePromptChain<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    prompts: Vec<ExecutablePromptVariant<'a, C, S>>,
+    defaults: GenerationParams,
+    #[cfg(feature = "timeout")]
+    timeout: Option<Duration>,
+}
+impl<'a, C, S> ExecutablePromptChain<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    pub fn new() -> Self {
+        ExecutablePromptChain {
+            prompts: Vec::new(),
+            defaults: GenerationParams::default(),
+            #[cfg(feature = "timeout")]
+            timeout: None,
+        }
+    }
+    pub fn push(&mut self, prompt: impl Into<ExecutablePromptVariant<'a, C, S>>) {
+        self.prompts.push(prompt.into());
+    }
+    //Applied to every step for the parameters the step leaves unset
+    pub fn with_defaults(mut self, defaults: GenerationParams) -> Self {
+        self.defaults = defaults;
+        self
+    }
+    pub fn set_defaults(&mut self, defaults: GenerationParams) {
+        self.defaults = defaults;
+    }
+    pub fn defaults(&self) -> &GenerationParams {
+        &self.defaults
+    }
+    //Budget of a whole run, counted from the first step of each flow
+    #[cfg(feature = "timeout")]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    #[cfg(feature = "timeout")]
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    #[cfg(feature = "timeout")]
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    pub fn variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.variables()).collect()
+    }
+    pub fn required_variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.required_variables()).collect()
+    }
+    //Every step is checked against the context as it is now, so values earlier steps would set count as missing
+    pub fn missing_variables(&self, context: &C) -> Vec<String> {
+        self.prompts.iter().fold(Vec::new(), |mut missing, prompt| {
+            merge_missing(&mut missing, prompt.missing_variables(context));
+            missing
+        })
+    }
+    pub fn validate(&self, context: &C) -> Result<(), PromptError> {
+        check_missing(self.missing_variables(context))
+    }
+    pub fn validate_type(&self) -> Result<(), PromptError> {
+        check_missing(undeclared::<C>(self.required_variables()))
+    }
+    pub fn flow(&'a self) -> ExecutableFlow<'a, C, S> {
+        ExecutableFlow {
+            prompts: self.prompts.iter().peekable(),
+            conversation: Conversation::new(),
+            defaults: &self.defaults,
+            usage: ChainUsage::default(),
+            #[cfg(feature = "timeout")]
+            timeout: self.timeout,
+            #[cfg(feature = "timeout")]
+            deadline: None,
+            #[cfg(feature = "timeout")]
+            cancel: None,
+            #[cfg(feature = "timeout")]
+            halted: false,
+        }
+    }
+}
+
+pub struct ExecutableFlow<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    prompts: Peekable<std::slice::Iter<'a, ExecutablePromptVariant<'a, C, S>>>,
+    conversation: Conversation,
+    defaults: &'a GenerationParams,
+    usage: ChainUsage,
+    #[cfg(feature = "timeout")]
+    timeout: Option<Duration>,
+    #[cfg(feature = "timeout")]
+    deadline: Option<Deadline>,
+    #[cfg(feature = "timeout")]
+    cancel: Option<CancellationToken>,
+    #[cfg(feature = "timeout")]
+    halted: bool, //cancelled or out of time, no further step runs
+}
+impl<'a, C, S> ExecutableFlow<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    pub fn with_conversation(mut self, conversation: Conversation) -> Self {
+        self.conversation = conversation;
+        self
+    }
+    //Cancelling the token aborts the running step with `PromptExecutableError::Cancelled` and stops the flow
+    #[cfg(feature = "timeout")]
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+    #[cfg(feature = "timeout")]
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+    pub fn conversation(&self) -> &Conversation {
+        &self.conversation
+    }
+    pub fn conversation_mut(&mut self) -> &mut Conversation {
+        &mut self.conversation
+    }
+    pub fn into_conversation(self) -> Conversation {
+        self.conversation
+    }
+    pub fn defaults(&self) -> &GenerationParams {
+        self.defaults
+    }
+    //What the steps executed so far spent, one entry per step
+    pub fn usage(&self) -> &ChainUsage {
+        &self.usage
+    }
+    pub fn next_with(&mut self, context: &C) -> Option<&PromptExecutableWithModel<'_, C, S>> {
+        self.next_direct(context)
+    }
+    //Steps the flow and executes the prompt, the exchange is recorded in the flow's conversation
+    pub async fn execute_next(&mut self, context: &mut C, backend: &dyn LlmBackend) -> Option<PromptResult<'a, S>> {
+        #[cfg(feature = "timeout")]
+        if self.halted {
+            return None;
+        }
+        let prompt = self.next_direct(context)?;
+        let result = prompt.run(context, backend, self.scope()).await;
+        self.usage.push(result.usage().clone());
+        #[cfg(feature = "timeout")]
+        self.check_interrupts();
+        Some(result)
+    }
+    //Same as `execute_next`, the step is retried as the policy says before the flow moves on
+    #[cfg(feature = "retry")]
+    pub async fn execute_next_with_retry(&mut self, context: &mut C, backend: &dyn LlmBackend, policy: impl Into<RetryPolicy>) -> Option<PromptResult<'a, S>> {
+        #[cfg(feature = "timeout")]
+        if self.halted {
+            return None;
+        }
+        let prompt = self.next_direct(context)?;
+        let result = prompt.run_with_retry(context, backend, self.scope(), &policy.into()).await;
+        self.usage.push(result.usage().clone());
+        #[cfg(feature = "timeout")]
+        self.check_interrupts();
+        Some(result)
+    }
+    fn scope(&mut self) -> ExecutionScope<'_> {
+        #[cfg(feature = "timeout")]
+        let deadline = self.timeout.map(|timeout| *self.deadline.get_or_insert_with(|| Deadline::after(timeout)));
+        ExecutionScope {
+            conversation: Some(&mut self.conversation),
+            defaults: Some(self.defaults),
+            #[cfg(feature = "timeout")]
+            interrupts: Interrupts {
+                cancel: self.cancel.as_ref(),
+                deadline,
+            },
+            ..Default::default()
+        }
+    }
+    #[cfg(feature = "timeout")]
+    fn check_interrupts(&mut self) {
+        self.halted = self.cancel.as_ref().is_some_and(CancellationToken::is_cancelled)
+            || self.deadline.as_ref().is_some_and(Deadline::is_expired);
+    }
+    fn next_direct(&mut self, context: &C) -> Option<&'a PromptExecutableWithModel<'a, C, S>> {
+        loop {
+            let mut final_break = false;
+            let mut in_loop = false;
+            let res = self.prompts.peek().and_then(|&prompt| {
+                let mut cur_prompt = prompt;
+                loop {
+                    match cur_prompt {
+                        ExecutablePromptVariant::Direct(_)=> {
+                            final_break = true;
+                            break Some(cur_prompt)
+                        },
+                        ExecutablePromptVariant::If(if_prompt) => {
+                            if if_prompt.get_condition()(context) {
+                                cur_prompt = if_prompt.get_then()
+                            } else {
+                                cur_prompt = if let Some(otherwise) = if_prompt.get_otherwise() {
+                                    otherwise
+                                } else {
+                                    break None
+                                }
+                            }
+                        },
+                        ExecutablePromptVariant::Loop(loop_prompt) => {
+                            if loop_prompt.get_condition()(context) {
+                                in_loop = true;
+                                cur_prompt = loop_prompt.get_prompt();
+                            } else {
+                                break None
+                            }
+                        }
+                    }
+                }
+            });
+            if final_break {
+                if !in_loop { //NOTE: a loop stays at the front until its condition fails
+                    self.prompts.next();
+                }
+                break match res.unwrap() { //NOTE: res must be Some if final_break is true
+                    ExecutablePromptVariant::Direct(prompt) => Some(prompt),
+                    _ => unreachable!() //NOTE: Variant must be Direct if final_break is true
+                }
+            }
+            //NOTE: an iteration that yields nothing ends the loop, the context can't change before the next check
+            if res.is_none() && self.prompts.next().is_none() {
+                break None
+            }
+        }
+    }
+}
+pub struct ExecutableIfPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    condition: Box<dyn Fn(&C) -> bool>,
+    then: ExecutablePromptVariant<'a, C, S>,
+    otherwise: Option<ExecutablePromptVariant<'a, C, S>>
+}
+impl<'a, C, S> ExecutableIfPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    pub fn new(condition: impl Fn(&C) -> bool + 'static, then: ExecutablePromptVariant<'a, C, S>, otherwise: Option<ExecutablePromptVariant<'a, C, S>>) -> Self {
+        ExecutableIfPrompt {
+            condition: Box::new(condition),
+            then,
+            otherwise,
+        }
+    }
+    pub fn get_then(&self) -> &ExecutablePromptVariant<'a, C, S> {
+        &self.then
+    }
+    pub fn get_otherwise(&self) -> Option<&ExecutablePromptVariant<'a, C, S>> {
+        self.otherwise.as_ref()
+    }
+    pub fn get_condition(&self) -> &dyn Fn(&C) -> bool {
+        &self.condition
+    }
+}
+pub struct ExecutableLoopPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    condition: Box<dyn Fn(&C) -> bool>,
+    prompt: ExecutablePromptVariant<'a, C, S>
+}
+impl<'a, C, S> ExecutableLoopPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    pub fn new(condition: impl Fn(&C) -> bool + 'static, prompt: ExecutablePromptVariant<'a, C, S>) -> Self {
+        ExecutableLoopPrompt {
+            condition: Box::new(condition),
+            prompt,
+        }
+    }
+    pub fn get_prompt(&self) -> &ExecutablePromptVariant<'a, C, S> {
+        &self.prompt
+    }
+    pub fn get_condition(&self) -> &dyn Fn(&C) -> bool {
+        &self.condition
+    }
+}
+pub enum ExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    Direct(PromptExecutableWithModel<'a, C, S>), //Naive and Template
+    If(Box<ExecutableIfPrompt<'a, C, S>>),
+    Loop(Box<ExecutableLoopPrompt<'a, C, S>>),
+}
+impl<C, S> ExecutablePromptVariant<'_, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    pub(crate) fn variables(&self) -> BTreeSet<&str> {
+        match self {
+            ExecutablePromptVariant::Direct(prompt) => prompt.inner_variant().variables(),
+            ExecutablePromptVariant::If(if_prompt) => {
+                let mut variables = if_prompt.then.variables();
+                variables.extend(if_prompt.otherwise.iter().flat_map(|otherwise| otherwise.variables()));
+                variables
+            },
+            ExecutablePromptVariant::Loop(loop_prompt) => loop_prompt.prompt.variables(),
+        }
+    }
+    pub(crate) fn required_variables(&self) -> BTreeSet<&str> {
+        match self {
+            ExecutablePromptVariant::Direct(prompt) => prompt.inner_variant().required_variables(),
+            ExecutablePromptVariant::If(if_prompt) => {
+                let mut variables = if_prompt.then.required_variables();
+                variables.extend(if_prompt.otherwise.iter().flat_map(|otherwise| otherwise.required_variables()));
+                variables
+            },
+            ExecutablePromptVariant::Loop(loop_prompt) => loop_prompt.prompt.required_variables(),
+        }
+    }
+    pub(crate) fn missing_variables(&self, context: &C) -> Vec<String> {
+        match self {
+            ExecutablePromptVariant::Direct(prompt) => prompt.inner_variant().missing_variables(context),
+            ExecutablePromptVariant::If(if_prompt) => {
+                if (if_prompt.condition)(context) {
+                    if_prompt.then.missing_variables(context)
+                } else {
+                    if_prompt.otherwise.as_ref().map(|otherwise| otherwise.missing_variables(context)).unwrap_or_default()
+                }
+            },
+            ExecutablePromptVariant::Loop(loop_prompt) if (loop_prompt.condition)(context) => loop_prompt.prompt.missing_variables(context),
+            ExecutablePromptVariant::Loop(_) => Vec::new(),
+        }
+    }
+}
+
+impl<'a, C, S> From<ExecutableIfPrompt<'a, C, S>> for ExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    fn from(if_prompt: ExecutableIfPrompt<'a, C, S>) -> Self {
+        ExecutablePromptVariant::If(Box::new(if_prompt))
+    }
+}
+impl<'a, C, S> From<ExecutableLoopPrompt<'a, C, S>> for ExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    fn from(loop_prompt: ExecutableLoopPrompt<'a, C, S>) -> Self {
+        ExecutablePromptVariant::Loop(Box::new(loop_prompt))
+    }
+}
+impl<'a, C, S> From<PromptExecutableWithModel<'a, C, S>> for ExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>
+{
+    fn from(prompt: PromptExecutableWithModel<'a, C, S>) -> Self {
+        ExecutablePromptVariant::Direct(prompt)
+    }
+}
+
+
+#[cfg(feature = "send")]
+#[derive(Default)]
+pub struct SendExecutablePromptChain<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> +  Send + Sync
+{
+    prompts: Vec<SendExecutablePromptVariant<'a, C, S>>,
+    defaults: GenerationParams,
+    #[cfg(feature = "timeout")]
+    timeout: Option<Duration>,
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> SendExecutablePromptChain<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> +  Send + Sync
+{
+    pub fn new() -> Self {
+        SendExecutablePromptChain {
+            prompts: Vec::new(),
+            defaults: GenerationParams::default(),
+            #[cfg(feature = "timeout")]
+            timeout: None,
+        }
+    }
+    pub fn push(&mut self, prompt: impl Into<SendExecutablePromptVariant<'a, C, S>>) {
+        self.prompts.push(prompt.into());
+    }
+    //Applied to every step for the parameters the step leaves unset
+    pub fn with_defaults(mut self, defaults: GenerationParams) -> Self {
+        self.defaults = defaults;
+        self
+    }
+    pub fn set_defaults(&mut self, defaults: GenerationParams) {
+        self.defaults = defaults;
+    }
+    pub fn defaults(&self) -> &GenerationParams {
+        &self.defaults
+    }
+    //Budget of a whole run, counted from the first step of each flow
+    #[cfg(feature = "timeout")]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    #[cfg(feature = "timeout")]
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    #[cfg(feature = "timeout")]
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    pub fn variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.variables()).collect()
+    }
+    pub fn required_variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.required_variables()).collect()
+    }
+    //Every step is checked against the context as it is now, so values earlier steps would set count as missing
+    pub fn missing_variables(&self, context: &C) -> Vec<String> {
+        self.prompts.iter().fold(Vec::new(), |mut missing, prompt| {
+            merge_missing(&mut missing, prompt.missing_variables(context));
+            missing
+        })
+    }
+    pub fn validate(&self, context: &C) -> Result<(), PromptError> {
+        check_missing(self.missing_variables(context))
+    }
+    pub fn validate_type(&self) -> Result<(), PromptError> {
+        check_missing(undeclared::<C>(self.required_variables()))
+    }
+    pub fn flow(&'a self) -> SendExecutableFlow<'a, C, S> {
+        SendExecutableFlow {
+            prompts: self.prompts.iter().peekable(),
+            conversation: Conversation::new(),
+            defaults: &self.defaults,
+            usage: ChainUsage::default(),
+            #[cfg(feature = "timeout")]
+            timeout: self.timeout,
+            #[cfg(feature = "timeout")]
+            deadline: None,
+            #[cfg(feature = "timeout")]
+            cancel: None,
+            #[cfg(feature = "timeout")]
+            halted: false,
+        }
+    }
+}
+#[cfg(feature = "send")]
+pub struct SendExecutableFlow<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> +  Send + Sync
+{
+    prompts: Peekable<std::slice::Iter<'a, SendExecutablePromptVariant<'a, C, S>>>,
+    conversation: Conversation,
+    defaults: &'a GenerationParams,
+    usage: ChainUsage,
+    #[cfg(feature = "timeout")]
+    timeout: Option<Duration>,
+    #[cfg(feature = "timeout")]
+    deadline: Option<Deadline>,
+    #[cfg(feature = "timeout")]
+    cancel: Option<CancellationToken>,
+    #[cfg(feature = "timeout")]
+    halted: bool, //cancelled or out of time, no further step runs
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> SendExecutableFlow<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> +  Send + Sync
+{
+    pub fn with_conversation(mut self, conversation: Conversation) -> Self {
+        self.conversation = conversation;
+        self
+    }
+    //Cancelling the token aborts the running step with `PromptExecutableError::Cancelled` and stops the flow
+    #[cfg(feature = "timeout")]
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+    #[cfg(feature = "timeout")]
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+    pub fn conversation(&self) -> &Conversation {
+        &self.conversation
+    }
+    pub fn conversation_mut(&mut self) -> &mut Conversation {
+        &mut self.conversation
+    }
+    pub fn into_conversation(self) -> Conversation {
+        self.conversation
+    }
+    pub fn defaults(&self) -> &GenerationParams {
+        self.defaults
+    }
+    //What the steps executed so far spent, one entry per step
+    pub fn usage(&self) -> &ChainUsage {
+        &self.usage
+    }
+    pub fn next_with(&mut self, context: &C) -> Option<&SendPromptExecutableWithModel<'_, C, S>> {
+        self.next_direct(context)
+    }
+    //Steps the flow and executes the prompt, the exchange is recorded in the flow's conversation
+    pub async fn execute_next(&mut self, context: &mut C, backend: &dyn LlmBackend) -> Option<SendPromptResult<'a, S>> {
+        #[cfg(feature = "timeout")]
+        if self.halted {
+            return None;
+        }
+        let prompt = self.next_direct(context)?;
+        let result = prompt.run(context, backend, self.scope()).await;
+        self.usage.push(result.usage().clone());
+        #[cfg(feature = "timeout")]
+        self.check_interrupts();
+        Some(result)
+    }
+    //Same as `execute_next`, the step is retried as the policy says before the flow moves on
+    #[cfg(feature = "retry")]
+    pub async fn execute_next_with_retry(&mut self, context: &mut C, backend: &dyn LlmBackend, policy: impl Into<RetryPolicy>) -> Option<SendPromptResult<'a, S>> {
+        #[cfg(feature = "timeout")]
+        if self.halted {
+            return None;
+        }
+        let prompt = self.next_direct(context)?;
+        let result = prompt.run_with_retry(context, backend, self.scope(), &policy.into()).await;
+        self.usage.push(result.usage().clone());
+        #[cfg(feature = "timeout")]
+        self.check_interrupts();
+        Some(result)
+    }
+    fn scope(&mut self) -> ExecutionScope<'_> {
+        #[cfg(feature = "timeout")]
+        let deadline = self.timeout.map(|timeout| *self.deadline.get_or_insert_with(|| Deadline::after(timeout)));
+        ExecutionScope {
+            conversation: Some(&mut self.conversation),
+            defaults: Some(self.defaults),
+            #[cfg(feature = "timeout")]
+            interrupts: Interrupts {
+                cancel: self.cancel.as_ref(),
+                deadline,
+            },
+            ..Default::default()
+        }
+    }
+    #[cfg(feature = "timeout")]
+    fn check_interrupts(&mut self) {
+        self.halted = self.cancel.as_ref().is_some_and(CancellationToken::is_cancelled)
+            || self.deadline.as_ref().is_some_and(Deadline::is_expired);
+    }
+    fn next_direct(&mut self, context: &C) -> Option<&'a SendPromptExecutableWithModel<'a, C, S>> {
+        loop {
+            let mut final_break = false;
+            let mut in_loop = false;
+            let res = self.prompts.peek().and_then(|&prompt| {
+                let mut cur_prompt = prompt;
+                loop {
+                    match cur_prompt {
+                        SendExecutablePromptVariant::Direct(_)=> {
+                            final_break = true;
+                            break Some(cur_prompt)
+                        },
+                        SendExecutablePromptVariant::If(if_prompt) => {
+                            if if_prompt.get_condition()(context) {
+                                cur_prompt = if_prompt.get_then()
+                            } else {
+                                cur_prompt = if let Some(otherwise) = if_prompt.get_otherwise() {
+                                    otherwise
+                                } else {
+                                    break None
+                                }
+                            }
+                        },
+                        SendExecutablePromptVariant::Loop(loop_prompt) => {
+                            if loop_prompt.get_condition()(context) {
+                                in_loop = true;
+                                cur_prompt = loop_prompt.get_prompt();
+                            } else {
+                                break None
+                            }
+                        }
+                    }
+                }
+            });
+            if final_break {
+                if !in_loop { //NOTE: a loop stays at the front until its condition fails
+                    self.prompts.next();
+                }
+                break match res.unwrap() { //NOTE: res must be Some if final_break is true
+                    SendExecutablePromptVariant::Direct(prompt) => Some(prompt),
+                    _ => unreachable!() //NOTE: Variant must be Direct if final_break is true
+                }
+            }
+            //NOTE: an iteration that yields nothing ends the loop, the context can't change before the next check
+            if res.is_none() && self.prompts.next().is_none() {
+                break None
+            }
+        }
+    }
+}
+#[cfg(feature = "send")]
+pub struct SendExecutableIfPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> +  Send + Sync
+{
+    condition: Box<dyn Fn(&C) -> bool>,
+    then: SendExecutablePromptVariant<'a, C, S>,
+    otherwise: Option<SendExecutablePromptVariant<'a, C, S>>
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> SendExecutableIfPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> +  Send + Sync
+{
+    pub fn new(condition: impl Fn(&C) -> bool + Send + Sync + 'static, then: SendExecutablePromptVariant<'a, C, S>, otherwise: Option<SendExecutablePromptVariant<'a, C, S>>) -> Self {
+        SendExecutableIfPrompt {
+            condition: Box::new(condition),
+            then,
+            otherwise,
+        }
+    }
+    pub fn get_then(&self) -> &SendExecutablePromptVariant<'a, C, S> {
+        &self.then
+    }
+    pub fn get_otherwise(&self) -> Option<&SendExecutablePromptVariant<'a, C, S>> {
+        self.otherwise.as_ref()
+    }
+    pub fn get_condition(&self) -> &dyn Fn(&C) -> bool {
+        &self.condition
+    }
+}
+#[cfg(feature = "send")]
+pub struct SendExecutableLoopPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> +  Send + Sync
+{
+    condition: Box<dyn Fn(&C) -> bool>,
+    prompt: SendExecutablePromptVariant<'a, C, S>
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> SendExecutableLoopPrompt<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync
+{
+    pub fn new(condition: impl Fn(&C) -> bool + Send + Sync + 'static, prompt: SendExecutablePromptVariant<'a, C, S>) -> Self {
+        SendExecutableLoopPrompt {
+            condition: Box::new(condition),
+            prompt,
+        }
+    }
+    pub fn get_prompt(&self) -> &SendExecutablePromptVariant<'a, C, S> {
+        &self.prompt
+    }
+    pub fn get_condition(&self) -> &dyn Fn(&C) -> bool {
+        &self.condition
+    }
+}
+#[cfg(feature = "send")]
+pub enum SendExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync
+{
+    Direct(SendPromptExecutableWithModel<'a, C, S>), //Naive and Template
+    If(Box<SendExecutableIfPrompt<'a, C, S>>),
+    Loop(Box<SendExecutableLoopPrompt<'a, C, S>>),
+}
+#[cfg(feature = "send")]
+impl<C, S> SendExecutablePromptVariant<'_, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync
+{
+    pub(crate) fn variables(&self) -> BTreeSet<&str> {
+        match self {
+            SendExecutablePromptVariant::Direct(prompt) => prompt.inner_variant().variables(),
+            SendExecutablePromptVariant::If(if_prompt) => {
+                let mut variables = if_prompt.then.variables();
+                variables.extend(if_prompt.otherwise.iter().flat_map(|otherwise| otherwise.variables()));
+                variables
+            },
+            SendExecutablePromptVariant::Loop(loop_prompt) => loop_prompt.prompt.variables(),
+        }
+    }
+    pub(crate) fn required_variables(&self) -> BTreeSet<&str> {
+        match self {
+            SendExecutablePromptVariant::Direct(prompt) => prompt.inner_variant().required_variables(),
+            SendExecutablePromptVariant::If(if_prompt) => {
+                let mut variables = if_prompt.then.required_variables();
+                variables.extend(if_prompt.otherwise.iter().flat_map(|otherwise| otherwise.required_variables()));
+                variables
+            },
+            SendExecutablePromptVariant::Loop(loop_prompt) => loop_prompt.prompt.required_variables(),
+        }
+    }
+    pub(crate) fn missing_variables(&self, context: &C) -> Vec<String> {
+        match self {
+            SendExecutablePromptVariant::Direct(prompt) => prompt.inner_variant().missing_variables(context),
+            SendExecutablePromptVariant::If(if_prompt) => {
+                if (if_prompt.condition)(context) {
+                    if_prompt.then.missing_variables(context)
+                } else {
+                    if_prompt.otherwise.as_ref().map(|otherwise| otherwise.missing_variables(context)).unwrap_or_default()
+                }
+            },
+            SendExecutablePromptVariant::Loop(loop_prompt) if (loop_prompt.condition)(context) => loop_prompt.prompt.missing_variables(context),
+            SendExecutablePromptVariant::Loop(_) => Vec::new(),
+        }
+    }
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> From<SendExecutableIfPrompt<'a, C, S>> for SendExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync
+{
+    fn from(if_prompt: SendExecutableIfPrompt<'a, C, S>) -> Self {
+        SendExecutablePromptVariant::If(Box::new(if_prompt))
+    }
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> From<SendExecutableLoopPrompt<'a, C, S>> for SendExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync
+{
+    fn from(loop_prompt: SendExecutableLoopPrompt<'a, C, S>) -> Self {
+        SendExecutablePromptVariant::Loop(Box::new(loop_prompt))
+    }
+}
+#[cfg(feature = "send")]
+impl<'a, C, S> From<SendPromptExecutableWithModel<'a, C, S>> for SendExecutablePromptVariant<'a, C, S>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync
+{
+    fn from(prompt: SendPromptExecutableWithModel<'a, C, S>) -> Self {
+        SendExecutablePromptVariant::Direct(prompt)
+    }
+}
+
+
+#[cfg(feature = "send")]
+#[derive(Default)]
+pub struct SendExecutableIfPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync,
+    U: Fn(&C) -> bool + 'static,
+{
+    then: Option<SendExecutablePromptVariant<'a, C, S>>,
+    otherwise: Option<SendExecutablePromptVariant<'a, C, S>>,
+    condition: Option<U>,
+}
+#[cfg(feature = "send")]
+impl<'a, C, S, U> SendExecutableIfPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync,
+    U: Fn(&C) -> bool + 'static
+{
+    pub fn new() -> Self {
+        SendExecutableIfPromptBuilder {
+            then: None,
+            otherwise: None,
+            condition: None,
+        }
+    }
+    pub fn build(self) -> Result<SendExecutableIfPrompt<'a, C, S>, IfPromptBuilderError> {
+        if self.condition.is_none() {
+            return Err(IfPromptBuilderError::MissingCondition);
+        }
+        if self.then.is_none() {
+            return Err(IfPromptBuilderError::MissingThen);
+        }
+        Ok(SendExecutableIfPrompt {
+            then: self.then.unwrap(),
+            otherwise: self.otherwise,
+            condition: Box::new(self.condition.unwrap()),
+        })
+    }
+    pub fn then(mut self, then: impl Into<SendExecutablePromptVariant<'a, C, S>>) -> Self {
+        self.then = Some(then.into());
+        self
+    }
+    pub fn otherwise(mut self, otherwise: impl Into<SendExecutablePromptVariant<'a, C, S>>) -> Self {
+        self.otherwise = Some(otherwise.into());
+        self
+    }
+    pub fn condition(mut self, condition: U) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+}
+#[cfg(feature = "send")]
+#[derive(Default)]
+pub struct SendExecutableLoopPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync,
+    U: Fn(&C) -> bool,
+{
+    prompt: Option<SendExecutablePromptVariant<'a, C, S>>,
+    condition: Option<U>,
+}
+#[cfg(feature = "send")]
+impl<'a, C, S, U> SendExecutableLoopPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de> + Send + Sync,
+    U: Fn(&C) -> bool + 'static,
+{
+    pub fn new() -> Self {
+        SendExecutableLoopPromptBuilder {
+            prompt: None,
+            condition: None,
+        }
+    }
+    pub fn build(self) -> Result<SendExecutableLoopPrompt<'a, C, S>, LoopPromptBuilderError> {
+        if self.condition.is_none() {
+            return Err(LoopPromptBuilderError::MissingCondition);
+        }
+        if self.prompt.is_none() {
+            return Err(LoopPromptBuilderError::MissingPrompt);
+        }
+        Ok(SendExecutableLoopPrompt {
+            prompt: self.prompt.unwrap(),
+            condition: Box::new(self.condition.unwrap()),
+        })
+    }
+    pub fn prompt(mut self, prompt: impl Into<SendExecutablePromptVariant<'a, C, S>>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+    pub fn condition(mut self, condition: U) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct ExecutableIfPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>,
+    U: Fn(&C) -> bool + 'static,
+{
+    then: Option<ExecutablePromptVariant<'a, C, S>>,
+    otherwise: Option<ExecutablePromptVariant<'a, C, S>>,
+    condition: Option<U>,
+}
+impl<'a, C, S, U> ExecutableIfPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>,
+    U: Fn(&C) -> bool + 'static
+{
+    pub fn new() -> Self {
+        ExecutableIfPromptBuilder {
+            then: None,
+            otherwise: None,
+            condition: None,
+        }
+    }
+    pub fn build(self) -> Result<ExecutableIfPrompt<'a, C, S>, IfPromptBuilderError> {
+        if self.condition.is_none() {
+            return Err(IfPromptBuilderError::MissingCondition);
+        }
+        if self.then.is_none() {
+            return Err(IfPromptBuilderError::MissingThen);
+        }
+        Ok(ExecutableIfPrompt {
+            then: self.then.unwrap(),
+            otherwise: self.otherwise,
+            condition: Box::new(self.condition.unwrap()),
+        })
+    }
+    pub fn then(mut self, then: impl Into<ExecutablePromptVariant<'a, C, S>>) -> Self {
+        self.then = Some(then.into());
+        self
+    }
+    pub fn otherwise(mut self, otherwise: impl Into<ExecutablePromptVariant<'a, C, S>>) -> Self {
+        self.otherwise = Some(otherwise.into());
+        self
+    }
+    pub fn condition(mut self, condition: U) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+}
+#[derive(Default)]
+pub struct ExecutableLoopPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>,
+    U: Fn(&C) -> bool,
+{
+    prompt: Option<ExecutablePromptVariant<'a, C, S>>,
+    condition: Option<U>,
+}
+impl<'a, C, S, U> ExecutableLoopPromptBuilder<'a, C, S, U>
+where
+    C: Context,
+    S: for<'de> Deserialize<'de>,
+    U: Fn(&C) -> bool + 'static,
+{
+    pub fn new() -> Self {
+        ExecutableLoopPromptBuilder {
+            prompt: None,
+            condition: None,
+        }
+    }
+    pub fn build(self) -> Result<ExecutableLoopPrompt<'a, C, S>, LoopPromptBuilderError> {
+        if self.condition.is_none() {
+            return Err(LoopPromptBuilderError::MissingCondition);
+        }
+        if self.prompt.is_none() {
+            return Err(LoopPromptBuilderError::MissingPrompt);
+        }
+        Ok(ExecutableLoopPrompt {
+            prompt: self.prompt.unwrap(),
+            condition: Box::new(self.condition.unwrap()),
+        })
+    }
+    pub fn prompt(mut self, prompt: impl Into<ExecutablePromptVariant<'a, C, S>>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+    pub fn condition(mut self, condition: U) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+}
\ No newline at end of file
@@ -0,0 +1,106 @@
+use std::future::{pending, poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use crate::feature::executor::prompt_result::PromptExecutableError;
+
+#[derive(Default)]
+struct Cancellation {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+//Cancels every execution it was handed to, clones share the same state.
+//In-flight requests are dropped, so nothing of the interrupted step is recorded.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Cancellation>,
+}
+impl CancellationToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn cancel(&self) {
+        if !self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            self.inner.notify.notify_waiters();
+        }
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+    pub async fn cancelled(&self) {
+        loop {
+            let mut notified = pin!(self.inner.notify.notified());
+            notified.as_mut().enable(); //NOTE: registered before checking, a cancel in between is not missed
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+//Time budget of a whole chain, set when its first step starts
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline {
+    at: Instant,
+    budget: Duration,
+}
+impl Deadline {
+    pub(crate) fn after(budget: Duration) -> Self {
+        Deadline {
+            at: Instant::now() + budget,
+            budget,
+        }
+    }
+    pub(crate) fn is_expired(&self) -> bool {
+        Instant::now() >= self.at
+    }
+}
+
+//What may stop an execution from outside
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Interrupts<'c> {
+    pub(crate) cancel: Option<&'c CancellationToken>,
+    pub(crate) deadline: Option<Deadline>,
+}
+impl Interrupts<'_> {
+    //Cancellation wins over the deadline, an already cancelled token never lets the execution start
+    pub(crate) async fn guard<T>(self, execution: impl Future<Output = Result<T, PromptExecutableError>>) -> Result<T, PromptExecutableError> {
+        let mut execution = pin!(execution);
+        let mut cancelled = pin!(async {
+            match self.cancel {
+                Some(cancel) => cancel.cancelled().await,
+                None => pending().await,
+            }
+        });
+        let mut expired = pin!(async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.at).await,
+                None => pending().await,
+            }
+        });
+        poll_fn(|cx| {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Err(PromptExecutableError::Cancelled));
+            }
+            if let (Poll::Ready(()), Some(deadline)) = (expired.as_mut().poll(cx), self.deadline) {
+                return Poll::Ready(Err(PromptExecutableError::Timeout(deadline.budget)));
+            }
+            execution.as_mut().poll(cx)
+        }).await
+    }
+}
+
+//Bounds a single attempt of a step
+pub(crate) async fn within<T>(timeout: Option<Duration>, attempt: impl Future<Output = Result<T, PromptExecutableError>>) -> Result<T, PromptExecutableError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, attempt).await
+            .unwrap_or(Err(PromptExecutableError::Timeout(timeout))),
+        None => attempt.await,
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "stream")]
+use crate::feature::executor::stream::{ChatDelta, ChatDeltaStream};
+use crate::feature::executor::backend::{ApiError, BackendError, BackendFuture, ChatChoice, ChatRequest, ChatResponse, FinishReason, LlmBackend, TokenUsage, ToolCall};
+
+enum ScriptedReply {
+    Choice(ChatChoice),
+    Response(ChatResponse),
+    Interrupted(String),
+    Error(BackendError),
+}
+struct ScriptedStep {
+    latency: Duration,
+    usage: Option<TokenUsage>,
+    reply: ScriptedReply,
+}
+
+//Answers requests with a queue of pre-recorded replies, in order, and keeps every request it saw.
+//Latency is slept with tokio, so paused test clocks make it deterministic.
+#[derive(Default)]
+pub struct ScriptedBackend {
+    script: Mutex<VecDeque<ScriptedStep>>,
+    requests: Mutex<Vec<ChatRequest>>,
+}
+impl ScriptedBackend {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    fn push(self, reply: ScriptedReply) -> Self {
+        self.script.lock().unwrap().push_back(ScriptedStep {
+            latency: Duration::ZERO,
+            usage: None,
+            reply,
+        });
+        self
+    }
+    fn choice(self, content: Option<String>, refusal: Option<String>, finish_reason: FinishReason) -> Self {
+        self.push(ScriptedReply::Choice(ChatChoice {
+            index: 0,
+            content,
+            refusal,
+            tool_calls: Vec::new(),
+            finish_reason: Some(finish_reason),
+        }))
+    }
+    pub fn reply(self, content: impl Into<String>) -> Self {
+        self.choice(Some(content.into()), None, FinishReason::Stop)
+    }
+    pub fn refuse(self, refusal: impl Into<String>) -> Self {
+        self.choice(None, Some(refusal.into()), FinishReason::Stop)
+    }
+    pub fn truncated(self, content: impl Into<String>) -> Self {
+        self.choice(Some(content.into()), None, FinishReason::Length)
+    }
+    pub fn filtered(self) -> Self {
+        self.choice(None, None, FinishReason::ContentFilter)
+    }
+    //Call ids are derived from the step, `call-<step>-<n>`
+    pub fn call_tools<N>(self, calls: impl IntoIterator<Item = (N, serde_json::Value)>) -> Self
+    where
+        N: Into<String>
+    {
+        let step = self.script.lock().unwrap().len();
+        let tool_calls = calls.into_iter()
+            .enumerate()
+            .map(|(n, (name, arguments))| ToolCall {
+                id: format!("call-{step}-{n}"),
+                name: name.into(),
+                arguments: arguments.to_string(),
+            })
+            .collect();
+        self.push(ScriptedReply::Choice(ChatChoice {
+            index: 0,
+            content: None,
+            refusal: None,
+            tool_calls,
+            finish_reason: Some(FinishReason::ToolCalls),
+        }))
+    }
+    pub fn call_tool(self, name: impl Into<String>, arguments: serde_json::Value) -> Self {
+        self.call_tools([(name.into(), arguments)])
+    }
+    pub fn respond(self, response: ChatResponse) -> Self {
+        self.push(ScriptedReply::Response(response))
+    }
+    pub fn fail(self, error: impl Into<BackendError>) -> Self {
+        self.push(ScriptedReply::Error(error.into()))
+    }
+    pub fn api_error(self, code: impl Into<String>) -> Self {
+        self.fail(ApiError::new("scripted api error").with_code(code))
+    }
+    pub fn rate_limited(self, retry_after: Duration) -> Self {
+        self.fail(ApiError::new("scripted rate limit").with_status(429).with_retry_after(retry_after))
+    }
+    //Streams the partial content then fails with a stream error, plain requests fail right away
+    pub fn interrupted(self, partial: impl Into<String>) -> Self {
+        self.push(ScriptedReply::Interrupted(partial.into()))
+    }
+    pub fn malformed_response(self) -> Self {
+        self.fail(BackendError::InvalidResponse("scripted malformed json".to_string()))
+    }
+    //Applies to the step pushed last
+    pub fn with_latency(self, latency: Duration) -> Self {
+        if let Some(step) = self.script.lock().unwrap().back_mut() {
+            step.latency = latency;
+        }
+        self
+    }
+    //Applies to the step pushed last
+    pub fn with_usage(self, prompt_tokens: u32, completion_tokens: u32) -> Self {
+        if let Some(step) = self.script.lock().unwrap().back_mut() {
+            step.usage = Some(TokenUsage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            });
+        }
+        self
+    }
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+    pub fn requested_models(&self) -> Vec<String> {
+        self.requests.lock().unwrap()
+            .iter()
+            .map(|request| request.model.clone())
+            .collect()
+    }
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+    //Records the request and plays the next step, after its latency
+    async fn play(&self, request: ChatRequest) -> Result<ScriptedReply, BackendError> {
+        let step = self.script.lock().unwrap().pop_front();
+        let model = request.model.clone();
+        let index = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(request);
+            requests.len()
+        };
+        let Some(step) = step else {
+            return Err(BackendError::Other("scripted backend has no reply left".into()));
+        };
+        if !step.latency.is_zero() {
+            tokio::time::sleep(step.latency).await;
+        }
+        match step.reply {
+            ScriptedReply::Choice(choice) => Ok(ScriptedReply::Response(ChatResponse {
+                id: format!("scripted-{index}"),
+                model,
+                choices: vec![choice],
+                usage: step.usage,
+            })),
+            ScriptedReply::Error(error) => Err(error),
+            reply => Ok(reply),
+        }
+    }
+}
+impl LlmBackend for ScriptedBackend {
+    fn chat(&self, request: ChatRequest) -> BackendFuture<'_, ChatResponse> {
+        Box::pin(async move {
+            match self.play(request).await? {
+                ScriptedReply::Response(response) => Ok(response),
+                ScriptedReply::Interrupted(partial) => Err(BackendError::Stream(format!("scripted stream interrupted after {partial:?}"))),
+                _ => unreachable!("played steps are either responses or interruptions"),
+            }
+        })
+    }
+    //Content is streamed word by word
+    #[cfg(feature = "stream")]
+    fn chat_stream(&self, request: ChatRequest) -> BackendFuture<'_, ChatDeltaStream<'_>> {
+        Box::pin(async move {
+            let (content, last) = match self.play(request).await? {
+                ScriptedReply::Response(response) => {
+                    let mut last = ChatDelta::from_response(response);
+                    (last.content.take().unwrap_or_default(), Ok(last))
+                },
+                ScriptedReply::Interrupted(partial) => (partial, Err(BackendError::Stream("scripted stream interrupted".to_string()))),
+                _ => unreachable!("played steps are either responses or interruptions"),
+            };
+            let mut deltas = content.split_inclusive(' ')
+                .map(|word| Ok(ChatDelta::content(word)))
+                .collect::<Vec<_>>();
+            deltas.push(last);
+            let deltas: ChatDeltaStream<'_> = Box::pin(futures::stream::iter(deltas));
+            Ok(deltas)
+        })
+    }
+}
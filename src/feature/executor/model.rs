@@ -0,0 +1,187 @@
+use crate::feature::executor::backend::{BackendError, ResponseFormat, TokenUsage};
+use crate::feature::executor::prompt_result::PromptExecutableError;
+
+//Price of a model, per million tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelCost {
+    pub input: f64,
+    pub output: f64,
+}
+impl ModelCost {
+    pub fn of(&self, tokens: &TokenUsage) -> f64 {
+        (f64::from(tokens.prompt_tokens) * self.input + f64::from(tokens.completion_tokens) * self.output) / 1_000_000.0
+    }
+}
+
+//A model and what it can do, unknown capabilities are assumed to be there
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSpec<'a> {
+    pub name: &'a str,
+    pub context_window: Option<u32>,
+    pub cost: Option<ModelCost>,
+    pub priority: i32, //higher is tried first
+    pub supports_json: bool,
+    pub supports_tools: bool,
+}
+impl<'a> ModelSpec<'a> {
+    pub fn new(name: &'a str) -> Self {
+        ModelSpec {
+            name,
+            context_window: None,
+            cost: None,
+            priority: 0,
+            supports_json: true,
+            supports_tools: true,
+        }
+    }
+    pub fn context_window(mut self, context_window: u32) -> Self {
+        self.context_window = Some(context_window);
+        self
+    }
+    pub fn cost(mut self, input: f64, output: f64) -> Self {
+        self.cost = Some(ModelCost { input, output });
+        self
+    }
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+    pub fn supports_json(mut self, supports_json: bool) -> Self {
+        self.supports_json = supports_json;
+        self
+    }
+    pub fn supports_tools(mut self, supports_tools: bool) -> Self {
+        self.supports_tools = supports_tools;
+        self
+    }
+    fn can_serve(&self, needs: &ModelNeeds) -> bool {
+        (!needs.json || self.supports_json) && (!needs.tools || self.supports_tools)
+    }
+}
+impl<'a> From<&'a str> for ModelSpec<'a> {
+    fn from(name: &'a str) -> Self {
+        ModelSpec::new(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fallback {
+    #[default]
+    Off, //only the selected model is tried
+    Downgrade, //on rate-limit, context-length or open circuit errors, the next model down the list is tried
+}
+
+//Models by descending priority, ties keep the order they were added in.
+//Models lacking a capability the request needs are always skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelList<'a> {
+    models: Vec<ModelSpec<'a>>,
+    fallback: Fallback,
+}
+impl<'a> ModelList<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn model(mut self, model: impl Into<ModelSpec<'a>>) -> Self {
+        let model = model.into();
+        let position = self.models.partition_point(|other| other.priority >= model.priority);
+        self.models.insert(position, model);
+        self
+    }
+    pub fn fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+    pub fn get_fallback(&self) -> Fallback {
+        self.fallback
+    }
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+    pub fn get(&self, index: usize) -> Option<&ModelSpec<'a>> {
+        self.models.get(index)
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, ModelSpec<'a>> {
+        self.models.iter()
+    }
+    pub fn names(&self) -> Vec<&'a str> {
+        self.models.iter().map(|model| model.name).collect()
+    }
+    //Indices of the models able to serve the request, in list order
+    #[cfg(feature = "retry")]
+    pub(crate) fn capable(&self, needs: &ModelNeeds) -> Vec<usize> {
+        (0..self.models.len())
+            .filter(|&index| self.models[index].can_serve(needs))
+            .collect()
+    }
+    //Models to try in turn: the selected one (or the first) and, when falling back, the ones after it
+    pub(crate) fn candidates(&self, select_model: Option<usize>, needs: &ModelNeeds) -> Result<Vec<usize>, PromptExecutableError> {
+        if self.models.is_empty() {
+            return Err(PromptExecutableError::ModelNotSet);
+        }
+        let start = select_model.unwrap_or(0);
+        if start >= self.models.len() {
+            return Err(PromptExecutableError::InvalidModelSelection(start));
+        }
+        let candidates = (start..self.models.len())
+            .filter(|&index| self.models[index].can_serve(needs))
+            .take(match self.fallback {
+                Fallback::Off => 1,
+                Fallback::Downgrade => usize::MAX,
+            })
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Err(PromptExecutableError::NoCapableModel);
+        }
+        Ok(candidates)
+    }
+    pub(crate) fn falls_back_on(&self, error: &PromptExecutableError) -> bool {
+        match (self.fallback, error) {
+            (Fallback::Downgrade, PromptExecutableError::Backend(BackendError::Api(e))) => {
+                e.matches_status(429) || e.is_context_length_exceeded()
+            },
+            (Fallback::Downgrade, PromptExecutableError::ContextWindowExceeded { .. }) => true,
+            #[cfg(feature = "retry")]
+            (Fallback::Downgrade, PromptExecutableError::Backend(BackendError::CircuitOpen { .. })) => true,
+            _ => false,
+        }
+    }
+}
+impl<'a> From<Vec<&'a str>> for ModelList<'a> {
+    fn from(models: Vec<&'a str>) -> Self {
+        models.into_iter().fold(ModelList::new(), ModelList::model)
+    }
+}
+impl<'a> From<Vec<ModelSpec<'a>>> for ModelList<'a> {
+    fn from(models: Vec<ModelSpec<'a>>) -> Self {
+        models.into_iter().fold(ModelList::new(), ModelList::model)
+    }
+}
+impl<'a> From<&'a str> for ModelList<'a> {
+    fn from(model: &'a str) -> Self {
+        ModelList::new().model(model)
+    }
+}
+impl<'a> From<ModelSpec<'a>> for ModelList<'a> {
+    fn from(model: ModelSpec<'a>) -> Self {
+        ModelList::new().model(model)
+    }
+}
+
+//What a request asks of the model serving it
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ModelNeeds {
+    pub(crate) json: bool,
+    pub(crate) tools: bool,
+}
+impl ModelNeeds {
+    pub(crate) fn of(response_format: Option<&ResponseFormat>, has_tools: bool) -> Self {
+        ModelNeeds {
+            json: matches!(response_format, Some(ResponseFormat::JsonObject | ResponseFormat::JsonSchema { .. })),
+            tools: has_tools,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use serde::de::DeserializeOwned;
+use crate::feature::executor::backend::{ChatResponse, FinishReason};
+use crate::feature::executor::prompt_result::ProcessorError;
+use crate::prompt::context::Context;
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+//Ready-made processors for `to_executable`, all of them go through `checked_content` first
+
+//The first choice's content, or why there is no usable content
+pub fn checked_content(response: &ChatResponse) -> Result<&str, ProcessorError> {
+    let choice = response.first_choice().ok_or(ProcessorError::EmptyResponse)?;
+    match &choice.finish_reason {
+        Some(FinishReason::ContentFilter) => return Err(ProcessorError::ContentFilter),
+        Some(FinishReason::Length) => return Err(ProcessorError::Truncated),
+        _ => {}
+    }
+    match (choice.content.as_deref(), &choice.refusal) {
+        (_, Some(refusal)) => Err(ProcessorError::Refusal(refusal.clone())),
+        (Some(content), None) if !content.trim().is_empty() => Ok(content),
+        _ => Err(ProcessorError::EmptyResponse),
+    }
+}
+
+pub fn text<C>(response: ChatResponse, _: &mut C) -> Result<Option<String>, ProcessorError> {
+    Ok(Some(checked_content(&response)?.to_string()))
+}
+
+pub fn json<C, S>(response: ChatResponse, _: &mut C) -> Result<Option<S>, ProcessorError>
+where
+    S: DeserializeOwned
+{
+    Ok(Some(serde_json::from_str(checked_content(&response)?)?))
+}
+
+//Takes the first ```json (or untagged) block, the whole content when there is none
+pub fn fenced_json<C, S>(response: ChatResponse, _: &mut C) -> Result<Option<S>, ProcessorError>
+where
+    S: DeserializeOwned
+{
+    let content = checked_content(&response)?;
+    Ok(Some(serde_json::from_str(fenced_block(content).unwrap_or(content))?))
+}
+
+fn fenced_block(content: &str) -> Option<&str> {
+    let mut blocks = content.split("```").skip(1).step_by(2);
+    blocks.find_map(|block| {
+        let (tag, body) = block.split_once('\n')?;
+        match tag.trim() {
+            "" | "json" | "JSON" => Some(body),
+            _ => None,
+        }
+    })
+}
+
+//One item per non-empty line, list markers like `-`, `*` or `1.` are removed
+pub fn lines<C>(response: ChatResponse, _: &mut C) -> Result<Option<Vec<String>>, ProcessorError> {
+    Ok(Some(checked_content(&response)?
+        .lines()
+        .map(strip_list_marker)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()))
+}
+
+fn strip_list_marker(line: &str) -> &str {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix(['-', '*', '+', '•']) {
+        return rest.trim_start();
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match line[digits..].strip_prefix(['.', ')']) {
+        Some(rest) if digits > 0 => rest.trim_start(),
+        _ => line,
+    }
+}
+
+//The content must be one of the labels, ignoring case, surrounding quotes and a final period
+pub fn label<C, S, L>(labels: impl IntoIterator<Item = (L, S)>) -> impl Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError>
+where
+    S: Clone,
+    L: Into<String>
+{
+    let labels = labels.into_iter()
+        .map(|(label, value)| (label.into(), value))
+        .collect::<Vec<(String, S)>>();
+    move |response, _| {
+        let answer = checked_content(&response)?
+            .trim()
+            .trim_end_matches('.')
+            .trim_matches(['"', '\'', '`'])
+            .trim();
+        labels.iter()
+            .find(|(label, _)| label.eq_ignore_ascii_case(answer))
+            .map(|(_, value)| Some(value.clone()))
+            .ok_or_else(|| {
+                let expected = labels.iter().map(|(label, _)| label.as_str()).collect::<Vec<_>>();
+                ProcessorError::validation(format!("expected one of {expected:?}, got {answer:?}"))
+            })
+    }
+}
+
+#[cfg(feature = "regex")]
+pub fn capture<C>(regex: Regex, group: usize) -> impl Fn(ChatResponse, &mut C) -> Result<Option<String>, ProcessorError> {
+    move |response, _| {
+        regex.captures(checked_content(&response)?)
+            .and_then(|captures| captures.get(group))
+            .map(|capture| Some(capture.as_str().to_string()))
+            .ok_or_else(|| ProcessorError::validation(format!("no match for `{}` (group {group})", regex.as_str())))
+    }
+}
+
+//Runs the processor and also writes its value into the context under `key`
+pub fn store_into<C, S, P>(key: impl Into<String>, processor: P) -> impl Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError>
+where
+    C: Context,
+    S: Clone + 'static,
+    P: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError>
+{
+    let key = key.into();
+    move |response, context| {
+        let value = processor(response, context)?;
+        if let Some(value) = &value && !context.set(&key, value.clone()) {
+            return Err(ProcessorError::custom(format!("context cannot store `{key}`")));
+        }
+        Ok(value)
+    }
+}
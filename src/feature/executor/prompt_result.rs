@@ -0,0 +1,125 @@
+use std::marker::PhantomData;
+#[cfg(feature = "timeout")]
+use std::time::Duration;
+use serde::Deserialize;
+use thiserror::Error;
+use crate::feature::executor::backend::BackendError;
+use crate::feature::executor::usage::StepUsage;
+use crate::prelude::PromptError;
+
+#[derive(Debug, Error)]
+pub enum PromptExecutableError
+{
+    #[error("model not set")]
+    ModelNotSet,
+    #[error("invalid model selection: {0}")]
+    InvalidModelSelection(usize),
+    #[error("no model supports what the request needs")]
+    NoCapableModel,
+    #[error("fail building prompt")]
+    FailBuildingPrompt(#[from] PromptError),
+    #[error("backend error: {0}")]
+    Backend(#[from] BackendError),
+    #[error("prompt takes {tokens} tokens, {model} has room for {limit}")]
+    ContextWindowExceeded { model: String, tokens: usize, limit: usize },
+    #[error("model still calling tools after {0} iterations")]
+    ToolIterationLimit(usize),
+    #[cfg(feature = "retry")]
+    #[error("gave up after running out of retries or time, last error: {0}")]
+    RetryFail(Box<PromptExecutableError>),
+    #[error("processor error: {0}")]
+    Processor(#[from] ProcessorError),
+    #[cfg(feature = "timeout")]
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
+    #[cfg(feature = "timeout")]
+    #[error("cancelled")]
+    Cancelled,
+}
+
+//Why a processor could not turn a response into a value
+#[derive(Debug, Error)]
+pub enum ProcessorError {
+    #[error("response has no content")]
+    EmptyResponse,
+    #[error("model refused: {0}")]
+    Refusal(String),
+    #[error("response withheld by the content filter")]
+    ContentFilter,
+    #[error("response cut off at the token limit")]
+    Truncated, //finish_reason=length
+    #[error("validation failed: {0}")]
+    Validation(String),
+    #[error("deserialize error: {0}")]
+    Deserialize(#[from] serde_json::Error),
+    #[error("{0}")]
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+}
+impl ProcessorError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        ProcessorError::Validation(message.into())
+    }
+    pub fn custom(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        ProcessorError::Custom(error.into())
+    }
+}
+
+pub struct PromptResult<'a, S>(Result<Option<S>, PromptExecutableError>, StepUsage, PhantomData<&'a ()>)
+where
+    S: Deserialize<'a>;
+impl<'a, S> PromptResult<'a, S>
+where
+    S: Deserialize<'a>
+{
+    pub fn is_ok(&self) -> bool {
+        self.0.is_ok()
+    }
+    pub fn is_err(&self) -> bool {
+        self.0.is_err()
+    }
+    //Tokens, latency and cost of every request the execution made, failed ones included
+    pub fn usage(&self) -> &StepUsage {
+        &self.1
+    }
+    pub(crate) fn with_usage(mut self, usage: StepUsage) -> Self {
+        self.1 = usage;
+        self
+    }
+    pub fn unwrap(self) -> Option<S> {
+        match self.0 {
+            Ok(s) => s,
+            Err(e) => panic!("PromptResult is err: {e}.")
+        }
+    }
+    pub fn unwrap_err(self) -> PromptExecutableError {
+        match self.0 {
+            Ok(_) => panic!("PromptResult is ok."),
+            Err(e) => e
+        }
+    }
+    pub fn into_result(self) -> Result<Option<S>, PromptExecutableError> {
+        self.0
+    }
+}
+impl<'a, S> From<Option<S>> for PromptResult<'a, S>
+where
+    S: Deserialize<'a>
+{
+    fn from(value: Option<S>) -> Self {
+        Self(Ok(value), StepUsage::default(), PhantomData)
+    }
+}
+impl<'a, S> PromptResult<'a, S>
+where
+
+    S: Deserialize<'a>
+{
+    pub fn ok(value: impl Into<Option<S>>) -> Self {
+        Self(Ok(value.into()), StepUsage::default(), PhantomData)
+    }
+    pub fn err(value: impl Into<PromptExecutableError>) -> Self {
+        Self(Err(value.into()), StepUsage::default(), PhantomData)
+    }
+}
+
+
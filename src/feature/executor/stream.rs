@@ -0,0 +1,213 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context as TaskContext, Poll};
+use futures::Stream;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use crate::feature::executor::backend::{BackendError, ChatChoice, ChatResponse, FinishReason, TokenUsage, ToolCall};
+use crate::feature::executor::prompt_result::PromptExecutableError;
+
+pub type ChatDeltaStream<'a> = Pin<Box<dyn Stream<Item = Result<ChatDelta, BackendError>> + Send + 'a>>;
+
+//One streamed chunk of the first choice
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChatDelta {
+    pub id: Option<String>,
+    pub model: Option<String>,
+    pub content: Option<String>,
+    pub refusal: Option<String>,
+    pub tool_calls: Vec<ToolCallDelta>,
+    pub finish_reason: Option<FinishReason>,
+    pub usage: Option<TokenUsage>,
+}
+impl ChatDelta {
+    pub fn content(content: impl Into<String>) -> Self {
+        ChatDelta {
+            content: Some(content.into()),
+            ..Default::default()
+        }
+    }
+    //A whole response as a single delta, for backends without native streaming
+    pub fn from_response(response: ChatResponse) -> Self {
+        let ChatResponse { id, model, choices, usage } = response;
+        let choice = choices.into_iter().next();
+        ChatDelta {
+            id: Some(id),
+            model: Some(model),
+            content: choice.as_ref().and_then(|choice| choice.content.clone()),
+            refusal: choice.as_ref().and_then(|choice| choice.refusal.clone()),
+            tool_calls: choice.as_ref()
+                .map(|choice| choice.tool_calls.iter()
+                    .enumerate()
+                    .map(|(index, call)| ToolCallDelta {
+                        index: index as u32,
+                        id: Some(call.id.clone()),
+                        name: Some(call.name.clone()),
+                        arguments: call.arguments.clone(),
+                    })
+                    .collect())
+                .unwrap_or_default(),
+            finish_reason: choice.and_then(|choice| choice.finish_reason),
+            usage,
+        }
+    }
+}
+
+//Tool calls arrive in pieces, `index` tells which call a piece belongs to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolCallDelta {
+    pub index: u32,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub arguments: String,
+}
+
+//Puts the deltas of a stream back together into the response `chat` would have returned
+#[derive(Debug, Default)]
+pub struct ResponseAssembler {
+    id: String,
+    model: String,
+    content: Option<String>,
+    refusal: Option<String>,
+    tool_calls: Vec<(u32, ToolCall)>,
+    finish_reason: Option<FinishReason>,
+    usage: Option<TokenUsage>,
+}
+impl ResponseAssembler {
+    pub fn new(model: impl Into<String>) -> Self {
+        ResponseAssembler {
+            model: model.into(),
+            ..Default::default()
+        }
+    }
+    pub fn push(&mut self, delta: ChatDelta) {
+        if let Some(id) = delta.id {
+            self.id = id;
+        }
+        if let Some(model) = delta.model {
+            self.model = model;
+        }
+        if let Some(content) = delta.content {
+            self.content.get_or_insert_default().push_str(&content);
+        }
+        if let Some(refusal) = delta.refusal {
+            self.refusal.get_or_insert_default().push_str(&refusal);
+        }
+        for call in delta.tool_calls {
+            let position = match self.tool_calls.iter().position(|(index, _)| *index == call.index) {
+                Some(position) => position,
+                None => {
+                    self.tool_calls.push((call.index, ToolCall {
+                        id: String::new(),
+                        name: String::new(),
+                        arguments: String::new(),
+                    }));
+                    self.tool_calls.len() - 1
+                }
+            };
+            let (_, tool_call) = &mut self.tool_calls[position];
+            if let Some(id) = call.id {
+                tool_call.id = id;
+            }
+            if let Some(name) = call.name {
+                tool_call.name.push_str(&name);
+            }
+            tool_call.arguments.push_str(&call.arguments);
+        }
+        if delta.finish_reason.is_some() {
+            self.finish_reason = delta.finish_reason;
+        }
+        if delta.usage.is_some() {
+            self.usage = delta.usage;
+        }
+    }
+    pub fn finish(self) -> ChatResponse {
+        ChatResponse {
+            id: self.id,
+            model: self.model,
+            choices: vec![ChatChoice {
+                index: 0,
+                content: self.content,
+                refusal: self.refusal,
+                tool_calls: self.tool_calls.into_iter().map(|(_, call)| call).collect(),
+                finish_reason: self.finish_reason,
+            }],
+            usage: self.usage,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StreamEvent<S> {
+    Delta(String),
+    Reset, //the attempt failed and will be retried, drop what was streamed so far
+    Done(Option<S>),
+}
+
+pub(crate) enum StreamChunk {
+    Delta(String),
+    Reset,
+}
+
+//Handed to the executor so it can forward deltas while it runs
+pub(crate) struct DeltaSink {
+    sender: UnboundedSender<StreamChunk>,
+    dirty: AtomicBool,
+}
+impl DeltaSink {
+    pub(crate) fn channel() -> (DeltaSink, UnboundedReceiver<StreamChunk>) {
+        let (sender, receiver) = unbounded();
+        (DeltaSink { sender, dirty: AtomicBool::new(false) }, receiver)
+    }
+    //A dropped stream just means nobody listens anymore, the execution still completes
+    pub(crate) fn delta(&self, content: String) {
+        self.dirty.store(true, Ordering::Relaxed);
+        let _ = self.sender.unbounded_send(StreamChunk::Delta(content));
+    }
+    //Only needed when something was streamed since the last reset
+    pub(crate) fn reset(&self) {
+        if self.dirty.swap(false, Ordering::Relaxed) {
+            let _ = self.sender.unbounded_send(StreamChunk::Reset);
+        }
+    }
+}
+
+//Drives the execution and yields its deltas, then the processed result as the last event
+pub struct PromptStream<F, S> {
+    execution: Option<Pin<Box<F>>>,
+    chunks: UnboundedReceiver<StreamChunk>,
+    result: Option<Result<Option<S>, PromptExecutableError>>,
+}
+impl<F, S> PromptStream<F, S>
+where
+    F: Future<Output = Result<Option<S>, PromptExecutableError>>
+{
+    pub(crate) fn new(execution: F, chunks: UnboundedReceiver<StreamChunk>) -> Self {
+        PromptStream {
+            execution: Some(Box::pin(execution)),
+            chunks,
+            result: None,
+        }
+    }
+}
+impl<F, S> Unpin for PromptStream<F, S> {} //NOTE: nothing is structurally pinned, the execution is boxed
+impl<F, S> Stream for PromptStream<F, S>
+where
+    F: Future<Output = Result<Option<S>, PromptExecutableError>>
+{
+    type Item = Result<StreamEvent<S>, PromptExecutableError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(execution) = &mut this.execution && let Poll::Ready(result) = execution.as_mut().poll(cx) {
+            this.result = Some(result);
+            this.execution = None; //NOTE: drops the sink, so the channel closes once drained
+        }
+        match Pin::new(&mut this.chunks).poll_next(cx) {
+            Poll::Ready(Some(StreamChunk::Delta(content))) => Poll::Ready(Some(Ok(StreamEvent::Delta(content)))),
+            Poll::Ready(Some(StreamChunk::Reset)) => Poll::Ready(Some(Ok(StreamEvent::Reset))),
+            Poll::Ready(None) => Poll::Ready(this.result.take().map(|result| result.map(StreamEvent::Done))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use serde::de::DeserializeOwned;
+use crate::feature::executor::backend::ChatResponse;
+use crate::feature::executor::processor::json;
+use crate::feature::executor::prompt_result::ProcessorError;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+#[cfg(feature = "schema")]
+use crate::feature::executor::backend::ResponseFormat;
+
+//Built-in processor, the first choice's content is the JSON of S
+pub fn structured_output<C, S>(response: ChatResponse, context: &mut C) -> Result<Option<S>, ProcessorError>
+where
+    S: DeserializeOwned
+{
+    json(response, context)
+}
+
+#[cfg(feature = "schema")]
+impl ResponseFormat {
+    //Providers only accept [a-zA-Z0-9_-] in the name, generic types need cleaning up
+    pub fn json_schema_for<S>() -> Self
+    where
+        S: JsonSchema
+    {
+        let name = S::schema_name()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect::<String>();
+        ResponseFormat::json_schema(name, schemars::schema_for!(S).to_value())
+    }
+}
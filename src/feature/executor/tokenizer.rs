@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::feature::executor::backend::{ChatMessage, Role};
+use crate::feature::executor::prompt_result::PromptExecutableError;
+use crate::prelude::{Context, Prompt, PromptError};
+use crate::prompt::value::TemplateValue;
+
+//Chat formats wrap every message in a few tokens of their own
+const MESSAGE_OVERHEAD: usize = 4;
+
+pub trait Tokenizer: Send + Sync {
+    fn count(&self, text: &str) -> usize;
+    //Longest prefix of `text` within `max_tokens`
+    fn truncate<'t>(&self, text: &'t str, max_tokens: usize) -> &'t str {
+        if self.count(text) <= max_tokens {
+            return text;
+        }
+        let boundaries = text.char_indices()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let fitting = boundaries.partition_point(|&end| self.count(&text[..end]) <= max_tokens);
+        &text[..boundaries[fitting.saturating_sub(1)]]
+    }
+    fn count_messages(&self, messages: &[ChatMessage]) -> usize {
+        messages.iter()
+            .map(|message| {
+                let calls = message.tool_calls.iter()
+                    .map(|call| self.count(&call.name) + self.count(&call.arguments))
+                    .sum::<usize>();
+                self.count(&message.content) + calls + MESSAGE_OVERHEAD
+            })
+            .sum()
+    }
+}
+
+//Roughly four characters a token for English text, errs on the high side for most other content
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeuristicTokenizer {
+    chars_per_token: f64,
+}
+impl Default for HeuristicTokenizer {
+    fn default() -> Self {
+        HeuristicTokenizer {
+            chars_per_token: 4.0,
+        }
+    }
+}
+impl HeuristicTokenizer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn chars_per_token(mut self, chars_per_token: f64) -> Self {
+        self.chars_per_token = chars_per_token.max(1.0);
+        self
+    }
+}
+impl Tokenizer for HeuristicTokenizer {
+    fn count(&self, text: &str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+}
+
+//What may be cut to make a request fit, tried in the order given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Truncation {
+    Var(String), //the end of a template variable's text, structured values can't be cut
+    History, //the oldest turns of the conversation, system messages are kept
+}
+
+//Checks each request against the context window of its model before it is sent,
+//models without a known window are never checked.
+#[derive(Clone)]
+pub struct TokenBudget {
+    tokenizer: Arc<dyn Tokenizer>,
+    reserve: Option<u32>, //left for the answer, `max_tokens` when unset
+    truncations: Vec<Truncation>,
+}
+impl Default for TokenBudget {
+    fn default() -> Self {
+        TokenBudget {
+            tokenizer: Arc::new(HeuristicTokenizer::new()),
+            reserve: None,
+            truncations: Vec::new(),
+        }
+    }
+}
+impl TokenBudget {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Arc::new(tokenizer);
+        self
+    }
+    pub fn reserve(mut self, reserve: u32) -> Self {
+        self.reserve = Some(reserve);
+        self
+    }
+    pub fn truncate_var(mut self, name: impl Into<String>) -> Self {
+        self.truncations.push(Truncation::Var(name.into()));
+        self
+    }
+    pub fn truncate_history(mut self) -> Self {
+        self.truncations.push(Truncation::History);
+        self
+    }
+    pub fn get_tokenizer(&self) -> &dyn Tokenizer {
+        &*self.tokenizer
+    }
+    pub fn get_truncations(&self) -> &[Truncation] {
+        &self.truncations
+    }
+    //Tokens the prompt may take in a window, once the answer's share is set aside
+    pub fn limit(&self, context_window: u32, max_tokens: Option<u32>) -> usize {
+        context_window.saturating_sub(self.reserve.or(max_tokens).unwrap_or(0)) as usize
+    }
+    //Renders the step, cutting what the truncations allow until history and step fit within `limit`
+    pub(crate) fn fit<C, P>(&self, prompt: &P, context: &C, history: &mut Vec<ChatMessage>, limit: usize, model: &str) -> Result<Vec<ChatMessage>, PromptExecutableError>
+    where
+        C: Context,
+        P: Prompt<C> + ?Sized
+    {
+        let mut overrides = HashMap::new();
+        let mut exchange = render(prompt, context, &overrides)?;
+        let count = |history: &[ChatMessage], exchange: &[ChatMessage]| {
+            self.tokenizer.count_messages(history) + self.tokenizer.count_messages(exchange)
+        };
+        let mut tokens = count(history, &exchange);
+        for truncation in &self.truncations {
+            if tokens <= limit {
+                break;
+            }
+            match truncation {
+                Truncation::History => {
+                    while tokens > limit && drop_oldest_turn(history) {
+                        tokens = count(history, &exchange);
+                    }
+                },
+                Truncation::Var(name) => {
+                    //NOTE: structured values are left whole, their text would break paths and loops over them
+                    let Some(TemplateValue::String(value)) = context.template_value(name) else {
+                        continue;
+                    };
+                    let mut keep = self.tokenizer.count(&value);
+                    while tokens > limit && keep > 0 {
+                        keep = keep.saturating_sub(tokens - limit);
+                        overrides.insert(name.clone(), self.tokenizer.truncate(&value, keep).to_string());
+                        exchange = render(prompt, context, &overrides)?;
+                        let before = std::mem::replace(&mut tokens, count(history, &exchange));
+                        if tokens >= before {
+                            break; //NOTE: the variable is not part of what was rendered
+                        }
+                    }
+                },
+            }
+        }
+        if tokens > limit {
+            return Err(PromptExecutableError::ContextWindowExceeded {
+                model: model.to_string(),
+                tokens,
+                limit,
+            });
+        }
+        Ok(exchange)
+    }
+}
+
+pub(crate) fn render<C, P>(prompt: &P, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<ChatMessage>, PromptError>
+where
+    C: Context,
+    P: Prompt<C> + ?Sized
+{
+    Ok(prompt.prompt_messages_with(context, overrides)?
+        .into_iter()
+        .map(|message| ChatMessage::new(message.role, message.content))
+        .collect())
+}
+
+//A turn runs from a user message to the next one, cutting whole turns keeps tool calls with their answers
+fn drop_oldest_turn(history: &mut Vec<ChatMessage>) -> bool {
+    let Some(start) = history.iter().position(|message| message.role != Role::System) else {
+        return false;
+    };
+    let end = history.iter()
+        .enumerate()
+        .skip(start + 1)
+        .find(|(_, message)| message.role == Role::User)
+        .map_or(history.len(), |(index, _)| index);
+    let mut index = 0;
+    history.retain(|message| {
+        let dropped = (start..end).contains(&index) && message.role != Role::System;
+        index += 1;
+        !dropped
+    });
+    true
+}
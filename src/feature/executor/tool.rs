@@ -0,0 +1,64 @@
+use std::fmt::Display;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use crate::feature::executor::backend::ToolDefinition;
+
+pub(crate) const DEFAULT_MAX_TOOL_ITERATIONS: usize = 8;
+
+pub type ToolHandler<'a, C> = dyn Fn(&str, &mut C) -> Result<String, String> + Send + Sync + 'a;
+
+//A Rust function the model may call, its arguments arrive as JSON matching `parameters`
+pub struct Tool<'a, C> {
+    definition: ToolDefinition,
+    handler: Box<ToolHandler<'a, C>>,
+}
+impl<'a, C> Tool<'a, C> {
+    pub fn new<A, R, E, F>(name: impl Into<String>, parameters: serde_json::Value, handler: F) -> Self
+    where
+        A: DeserializeOwned,
+        R: Serialize,
+        E: Display,
+        F: Fn(A, &mut C) -> Result<R, E> + Send + Sync + 'a
+    {
+        Tool {
+            definition: ToolDefinition {
+                name: name.into(),
+                description: None,
+                parameters,
+            },
+            handler: Box::new(move |arguments, context| {
+                let arguments = serde_json::from_str(arguments)
+                    .map_err(|e| format!("invalid arguments: {e}"))?;
+                let output = handler(arguments, context).map_err(|e| e.to_string())?;
+                serde_json::to_string(&output).map_err(|e| e.to_string())
+            }),
+        }
+    }
+    //The parameters schema is derived from the argument type
+    #[cfg(feature = "schema")]
+    pub fn typed<A, R, E, F>(name: impl Into<String>, handler: F) -> Self
+    where
+        A: DeserializeOwned + JsonSchema,
+        R: Serialize,
+        E: Display,
+        F: Fn(A, &mut C) -> Result<R, E> + Send + Sync + 'a
+    {
+        Self::new(name, schemars::schema_for!(A).to_value(), handler)
+    }
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.definition.description = Some(description.into());
+        self
+    }
+    pub fn definition(&self) -> &ToolDefinition {
+        &self.definition
+    }
+    //Failures are sent back to the model as the tool's output, so it gets a chance to correct itself
+    pub fn call(&self, arguments: &str, context: &mut C) -> String {
+        (self.handler)(arguments, context).unwrap_or_else(Self::failure)
+    }
+    pub(crate) fn failure(error: String) -> String {
+        serde_json::json!({ "error": error }).to_string()
+    }
+}
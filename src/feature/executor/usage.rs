@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::feature::executor::backend::TokenUsage;
+use crate::feature::executor::model::{ModelCost, ModelList};
+
+//Prices by model name, for models the list gave no cost or to reprice a run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelCost>,
+}
+impl PriceTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    //Per million tokens
+    pub fn model(mut self, model: impl Into<String>, input: f64, output: f64) -> Self {
+        self.prices.insert(model.into(), ModelCost { input, output });
+        self
+    }
+    pub fn get(&self, model: &str) -> Option<ModelCost> {
+        self.prices.get(model).copied()
+    }
+}
+impl From<&ModelList<'_>> for PriceTable {
+    fn from(models: &ModelList<'_>) -> Self {
+        models.iter()
+            .filter_map(|model| model.cost.map(|cost| (model.name, cost)))
+            .fold(PriceTable::new(), |table, (name, cost)| table.model(name, cost.input, cost.output))
+    }
+}
+
+//One request sent to the backend, failed ones included
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallUsage {
+    pub model: String,
+    pub tokens: Option<TokenUsage>, //not every provider or failure reports it
+    pub latency: Duration,
+    pub cost: Option<f64>, //from the cost of the model in the list
+}
+impl CallUsage {
+    pub fn cost_with(&self, prices: &PriceTable) -> Option<f64> {
+        match (prices.get(&self.model), &self.tokens) {
+            (Some(price), Some(tokens)) => Some(price.of(tokens)),
+            _ => self.cost,
+        }
+    }
+}
+
+fn sum_tokens<'u>(tokens: impl Iterator<Item = &'u TokenUsage>) -> TokenUsage {
+    tokens.fold(TokenUsage::default(), |sum, tokens| TokenUsage {
+        prompt_tokens: sum.prompt_tokens + tokens.prompt_tokens,
+        completion_tokens: sum.completion_tokens + tokens.completion_tokens,
+        total_tokens: sum.total_tokens + tokens.total_tokens,
+    })
+}
+
+//Every request a step made: tool rounds, fallbacks and retries
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepUsage {
+    calls: Vec<CallUsage>,
+}
+impl StepUsage {
+    pub fn calls(&self) -> &[CallUsage] {
+        &self.calls
+    }
+    pub fn tokens(&self) -> TokenUsage {
+        sum_tokens(self.calls.iter().filter_map(|call| call.tokens.as_ref()))
+    }
+    pub fn latency(&self) -> Duration {
+        self.calls.iter().map(|call| call.latency).sum()
+    }
+    //Calls without a known price count for nothing
+    pub fn cost(&self) -> f64 {
+        self.calls.iter().filter_map(|call| call.cost).sum()
+    }
+    pub fn cost_with(&self, prices: &PriceTable) -> f64 {
+        self.calls.iter().filter_map(|call| call.cost_with(prices)).sum()
+    }
+    //The model of the last request, the one that answered when the step succeeded
+    pub fn model(&self) -> Option<&str> {
+        self.calls.last().map(|call| call.model.as_str())
+    }
+}
+
+//Every step of a flow run so far, in the order they ran
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChainUsage {
+    steps: Vec<StepUsage>,
+}
+impl ChainUsage {
+    pub fn steps(&self) -> &[StepUsage] {
+        &self.steps
+    }
+    pub fn tokens(&self) -> TokenUsage {
+        sum_tokens(self.steps.iter().flat_map(|step| step.calls.iter().filter_map(|call| call.tokens.as_ref())))
+    }
+    pub fn latency(&self) -> Duration {
+        self.steps.iter().map(StepUsage::latency).sum()
+    }
+    pub fn cost(&self) -> f64 {
+        self.steps.iter().map(StepUsage::cost).sum()
+    }
+    pub fn cost_with(&self, prices: &PriceTable) -> f64 {
+        self.steps.iter().map(|step| step.cost_with(prices)).sum()
+    }
+    pub(crate) fn push(&mut self, step: StepUsage) {
+        self.steps.push(step);
+    }
+}
+
+//Collects the calls of one execution while it runs, so an interrupted one still accounts for what it spent
+#[derive(Default)]
+pub(crate) struct UsageRecorder(Mutex<StepUsage>);
+impl UsageRecorder {
+    //Carries on from the calls of earlier attempts
+    #[cfg(all(feature = "retry", feature = "send"))]
+    pub(crate) fn continuing(usage: StepUsage) -> Self {
+        UsageRecorder(Mutex::new(usage))
+    }
+    pub(crate) fn record(&self, call: CallUsage) {
+        self.0.lock().unwrap().calls.push(call);
+    }
+    pub(crate) fn finish(self) -> StepUsage {
+        self.0.into_inner().unwrap()
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+//How long a server asked us to wait, from the headers of a failed response.
+//`retry-after-ms` is the most precise, then `retry-after`, then the longest `x-ratelimit-reset-*`.
+pub(crate) fn from_headers<'h, I>(headers: I) -> Option<Duration>
+where
+    I: IntoIterator<Item = (&'h str, &'h str)>
+{
+    let mut retry_after_ms = None;
+    let mut retry_after = None;
+    let mut reset: Option<Duration> = None;
+    for (name, value) in headers {
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+        if name == "retry-after-ms" {
+            retry_after_ms = seconds(value).map(|ms| ms / 1000);
+        } else if name == "retry-after" {
+            retry_after = seconds(value).or_else(|| http_date(value, SystemTime::now()));
+        } else if name.starts_with("x-ratelimit-reset") && let Some(wait) = duration(value) {
+            reset = Some(reset.map_or(wait, |reset| reset.max(wait)));
+        }
+    }
+    retry_after_ms.or(retry_after).or(reset)
+}
+
+//Some providers only tell it in the message: "... Please try again in 6.5s."
+#[cfg(any(test, feature = "async_oai"))]
+pub(crate) fn from_message(message: &str) -> Option<Duration> {
+    let (_, rest) = message.split_once("try again in ")?;
+    let end = rest.find(|c: char| c.is_whitespace() || c == ',')
+        .unwrap_or(rest.len());
+    duration(rest[..end].trim_end_matches('.'))
+}
+
+fn seconds(value: &str) -> Option<Duration> {
+    value.parse::<f64>().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+//Go style durations as sent by OpenAI ("1s", "6m0s", "20ms", "1h2m3.5s"), or plain seconds
+fn duration(value: &str) -> Option<Duration> {
+    if value.is_empty() {
+        return None;
+    }
+    if let Some(plain) = seconds(value) {
+        return Some(plain);
+    }
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let (number, unit_and_rest) = rest.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let unit_len = unit_and_rest.find(|c: char| c.is_ascii_digit())
+            .unwrap_or(unit_and_rest.len());
+        let (unit, next) = unit_and_rest.split_at(unit_len);
+        total += number * match unit {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            "us" | "µs" => 0.000_001,
+            "ns" => 0.000_000_001,
+            _ => return None,
+        };
+        rest = next;
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+//IMF-fixdate, the only format servers may still send: "Wed, 21 Oct 2015 07:28:00 GMT"
+fn http_date(value: &str, now: SystemTime) -> Option<Duration> {
+    let mut parts = value.split_whitespace().skip(1);
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?;
+    let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"]
+        .iter()
+        .position(|month| *month == month_name)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if parts.next()? != "GMT" {
+        return None;
+    }
+    let at = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    //A date in the past means no wait at all
+    Some(Duration::from_secs(at.saturating_sub(now).max(0) as u64))
+}
+
+//Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
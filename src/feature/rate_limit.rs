@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(feature = "stream")]
+use futures::StreamExt;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+use crate::feature::executor::backend::{BackendFuture, ChatRequest, ChatResponse, LlmBackend, TokenUsage};
+#[cfg(feature = "stream")]
+use crate::feature::executor::stream::ChatDeltaStream;
+
+//Quotas of one model, unset ones are not enforced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+    pub max_in_flight: Option<usize>,
+}
+impl RateLimits {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn requests_per_minute(mut self, requests_per_minute: u32) -> Self {
+        self.requests_per_minute = Some(requests_per_minute);
+        self
+    }
+    pub fn tokens_per_minute(mut self, tokens_per_minute: u32) -> Self {
+        self.tokens_per_minute = Some(tokens_per_minute);
+        self
+    }
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+}
+
+//Refills continuously up to a minute's worth, going negative when a request used more than it reserved
+struct Bucket {
+    capacity: f64,
+    per_second: f64,
+    available: f64,
+    updated: Instant,
+}
+impl Bucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = f64::from(limit.max(1));
+        Bucket {
+            capacity,
+            per_second: capacity / 60.0,
+            available: capacity,
+            updated: Instant::now(),
+        }
+    }
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+    //Zero when `amount` can be taken right away
+    fn wait_for(&self, amount: f64) -> Duration {
+        //A request bigger than the whole bucket only waits for a full one
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+}
+
+struct ModelLimiter {
+    buckets: Mutex<Buckets>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+impl ModelLimiter {
+    fn new(limits: RateLimits) -> Self {
+        ModelLimiter {
+            buckets: Mutex::new(Buckets {
+                requests: limits.requests_per_minute.map(Bucket::per_minute),
+                tokens: limits.tokens_per_minute.map(Bucket::per_minute),
+            }),
+            in_flight: limits.max_in_flight.map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+    //Takes a slot first, so requests queued on concurrency don't hold quota they can't use yet
+    async fn acquire(&self, tokens: u32) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.in_flight {
+            Some(semaphore) => Some(semaphore.clone().acquire_owned().await.expect("semaphore is never closed")),
+            None => None,
+        };
+        let tokens = f64::from(tokens);
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap();
+                let now = Instant::now();
+                let Buckets { requests, tokens: token_bucket } = &mut *buckets;
+                requests.iter_mut().chain(token_bucket.iter_mut()).for_each(|bucket| bucket.refill(now));
+                let wait = requests.as_ref().map_or(Duration::ZERO, |bucket| bucket.wait_for(1.0))
+                    .max(token_bucket.as_ref().map_or(Duration::ZERO, |bucket| bucket.wait_for(tokens)));
+                if wait.is_zero() {
+                    if let Some(bucket) = requests {
+                        bucket.available -= 1.0;
+                    }
+                    if let Some(bucket) = token_bucket {
+                        bucket.available -= tokens;
+                    }
+                }
+                wait
+            };
+            if wait.is_zero() {
+                return permit;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+    //Settles the reservation with what the provider says was used
+    fn settle(&self, reserved: u32, usage: Option<TokenUsage>) {
+        let Some(usage) = usage else {
+            return;
+        };
+        if let Some(bucket) = &mut self.buckets.lock().unwrap().tokens {
+            let extra = f64::from(usage.total_tokens) - f64::from(reserved);
+            bucket.available = (bucket.available - extra).min(bucket.capacity);
+        }
+    }
+}
+
+//Client side quotas per model, shared by every clone and every backend it wraps,
+//so all chains running against one provider stay under its limits together.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    defaults: RateLimits,
+    models: HashMap<String, RateLimits>,
+    limiters: Arc<Mutex<HashMap<String, Arc<ModelLimiter>>>>,
+}
+impl RateLimiter {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    //For the models without limits of their own
+    pub fn default_limits(mut self, limits: RateLimits) -> Self {
+        self.defaults = limits;
+        self
+    }
+    pub fn model(mut self, model: impl Into<String>, limits: RateLimits) -> Self {
+        self.models.insert(model.into(), limits);
+        self
+    }
+    pub fn limits(&self, model: &str) -> RateLimits {
+        self.models.get(model).copied().unwrap_or(self.defaults)
+    }
+    pub fn wrap<B>(&self, backend: B) -> RateLimitedBackend<B>
+    where
+        B: LlmBackend
+    {
+        RateLimitedBackend {
+            backend,
+            limiter: self.clone(),
+        }
+    }
+    fn limiter(&self, model: &str) -> Arc<ModelLimiter> {
+        self.limiters.lock().unwrap()
+            .entry(model.to_string())
+            .or_insert_with(|| Arc::new(ModelLimiter::new(self.limits(model))))
+            .clone()
+    }
+}
+
+//Providers count the prompt plus the completion budget against tokens-per-minute
+fn estimate_tokens(request: &ChatRequest) -> u32 {
+    let chars = request.messages.iter()
+        .map(|message| message.content.chars().count())
+        .sum::<usize>();
+    let prompt = u32::try_from(chars.div_ceil(4)).unwrap_or(u32::MAX);
+    prompt.saturating_add(request.params.max_tokens.unwrap_or(0))
+}
+
+pub struct RateLimitedBackend<B> {
+    backend: B,
+    limiter: RateLimiter,
+}
+impl<B> RateLimitedBackend<B> {
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+}
+impl<B> LlmBackend for RateLimitedBackend<B>
+where
+    B: LlmBackend
+{
+    fn chat(&self, request: ChatRequest) -> BackendFuture<'_, ChatResponse> {
+        Box::pin(async move {
+            let limiter = self.limiter.limiter(&request.model);
+            let reserved = estimate_tokens(&request);
+            let _permit = limiter.acquire(reserved).await;
+            let response = self.backend.chat(request).await?;
+            limiter.settle(reserved, response.usage);
+            Ok(response)
+        })
+    }
+    //The in-flight slot is held until the stream is dropped
+    #[cfg(feature = "stream")]
+    fn chat_stream(&self, request: ChatRequest) -> BackendFuture<'_, ChatDeltaStream<'_>> {
+        Box::pin(async move {
+            let limiter = self.limiter.limiter(&request.model);
+            let reserved = estimate_tokens(&request);
+            let permit = limiter.acquire(reserved).await;
+            let deltas = self.backend.chat_stream(request).await?;
+            let deltas: ChatDeltaStream<'_> = Box::pin(deltas.inspect(move |delta| {
+                let _holding = &permit;
+                if let Ok(delta) = delta && delta.usage.is_some() {
+                    limiter.settle(reserved, delta.usage);
+                }
+            }));
+            Ok(deltas)
+        })
+    }
+}
@@ -1,5 +1,13 @@
-
-mod retry;
-pub use retry::*;
+#[cfg(feature = "send")]
+mod strategy;
+#[cfg(feature = "send")]
+pub use strategy::*;
+mod policy;
+pub use policy::*;
+mod circuit_breaker;
+pub use circuit_breaker::*;
+pub(crate) mod retrier;
+#[cfg(feature = "send")]
 pub mod result;
+#[cfg(feature = "send")]
 pub mod executable;
\ No newline at end of file
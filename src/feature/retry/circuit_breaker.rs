@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(feature = "stream")]
+use futures::StreamExt;
+use tokio::time::Instant;
+use crate::feature::executor::backend::{BackendError, BackendFuture, ChatRequest, ChatResponse, LlmBackend};
+#[cfg(feature = "stream")]
+use crate::feature::executor::stream::ChatDeltaStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed, //requests go through
+    Open, //requests fail at once until the cooldown is over
+    HalfOpen, //one probe request may go through, its outcome closes or reopens the circuit
+}
+
+enum Circuit {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probe: Option<Instant> }, //a probe that never reports back is given up after a cooldown
+}
+
+//Failure bookkeeping per model, shared by every clone and every backend it wraps,
+//so once a model is down the following steps and chains stop paying for it.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+}
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+            circuits: Default::default(),
+        }
+    }
+}
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    //Consecutive failures that open the circuit
+    pub fn failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+    pub fn wrap<B>(&self, backend: B) -> CircuitBreakerBackend<B>
+    where
+        B: LlmBackend
+    {
+        CircuitBreakerBackend {
+            backend,
+            breaker: self.clone(),
+        }
+    }
+    pub fn state(&self, model: &str) -> CircuitState {
+        match self.circuits.lock().unwrap().get(model) {
+            None | Some(Circuit::Closed { .. }) => CircuitState::Closed,
+            Some(Circuit::Open { until }) if Instant::now() < *until => CircuitState::Open,
+            Some(Circuit::Open { .. } | Circuit::HalfOpen { .. }) => CircuitState::HalfOpen,
+        }
+    }
+    pub fn reset(&self, model: &str) {
+        self.circuits.lock().unwrap().remove(model);
+    }
+    //Err holds how long until a request may go through again
+    fn acquire(&self, model: &str) -> Result<(), Duration> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(model) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        match circuit {
+            Circuit::Closed { .. } => Ok(()),
+            Circuit::Open { until } if now < *until => Err(*until - now),
+            Circuit::HalfOpen { probe: Some(started) } if now < *started + self.cooldown => {
+                Err(*started + self.cooldown - now)
+            },
+            _ => {
+                *circuit = Circuit::HalfOpen { probe: Some(now) };
+                Ok(())
+            },
+        }
+    }
+    fn record(&self, model: &str, failed: bool) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(model.to_string()).or_insert(Circuit::Closed { failures: 0 });
+        *circuit = match (failed, &*circuit) {
+            (false, _) => Circuit::Closed { failures: 0 },
+            (true, Circuit::Closed { failures }) if failures + 1 < self.failure_threshold => {
+                Circuit::Closed { failures: failures + 1 }
+            },
+            (true, _) => Circuit::Open { until: Instant::now() + self.cooldown },
+        };
+    }
+}
+
+//Only failures telling the model is unreachable or broken count, a rejected request means it is up
+fn is_outage(error: &BackendError) -> bool {
+    match error {
+        BackendError::Transport(_) | BackendError::InvalidResponse(_) | BackendError::Stream(_) => true,
+        BackendError::Api(e) => e.status.is_some_and(|status| status >= 500)
+            || [500, 502, 503, 504].into_iter().any(|status| e.matches_status(status)),
+        _ => false,
+    }
+}
+
+pub struct CircuitBreakerBackend<B> {
+    backend: B,
+    breaker: CircuitBreaker,
+}
+impl<B> CircuitBreakerBackend<B> {
+    pub fn breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+}
+impl<B> LlmBackend for CircuitBreakerBackend<B>
+where
+    B: LlmBackend
+{
+    fn chat(&self, request: ChatRequest) -> BackendFuture<'_, ChatResponse> {
+        Box::pin(async move {
+            let model = request.model.clone();
+            if let Err(retry_after) = self.breaker.acquire(&model) {
+                return Err(BackendError::CircuitOpen { model, retry_after });
+            }
+            let result = self.backend.chat(request).await;
+            self.breaker.record(&model, result.as_ref().is_err_and(is_outage));
+            result
+        })
+    }
+    //Opening the stream counts as a success, an error halfway through as a failure
+    #[cfg(feature = "stream")]
+    fn chat_stream(&self, request: ChatRequest) -> BackendFuture<'_, ChatDeltaStream<'_>> {
+        Box::pin(async move {
+            let model = request.model.clone();
+            if let Err(retry_after) = self.breaker.acquire(&model) {
+                return Err(BackendError::CircuitOpen { model, retry_after });
+            }
+            let deltas = self.backend.chat_stream(request).await;
+            self.breaker.record(&model, deltas.as_ref().is_err_and(is_outage));
+            let breaker = &self.breaker;
+            let deltas: ChatDeltaStream<'_> = Box::pin(deltas?.inspect(move |delta| {
+                if let Err(e) = delta && is_outage(e) {
+                    breaker.record(&model, true);
+                }
+            }));
+            Ok(deltas)
+        })
+    }
+}
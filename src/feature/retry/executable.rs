@@ -1,9 +1,24 @@
 use serde::Deserialize;
-use async_openai::types::{ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs, CreateChatCompletionResponse};
-use async_openai::Client;
-use async_openai::config::OpenAIConfig;
-use crate::prelude::{Context, Prompt, RetryablePromptResult, SendPromptVariant};
-use crate::prelude::PromptExecutableError::{InvalidModelSelection, ModelNotSet};
+#[cfg(feature = "schema")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use crate::feature::executor::backend::{ChatResponse, LlmBackend, ResponseFormat};
+use crate::feature::executor::executable::{execute_prompt, settings_methods, ExecutableSettings, ExecutionScope, SendProcessor};
+use crate::feature::executor::prompt_result::ProcessorError;
+#[cfg(feature = "stream")]
+use crate::feature::executor::prompt_result::PromptExecutableError;
+use crate::feature::executor::model::ModelList;
+#[cfg(feature = "schema")]
+use crate::feature::executor::structured::structured_output;
+use crate::feature::executor::conversation::Conversation;
+use crate::feature::executor::usage::{StepUsage, UsageRecorder};
+use crate::feature::retry::result::RetryableExecuteError;
+use crate::prelude::{Context, RetryablePromptResult, SendPromptVariant};
+#[cfg(feature = "stream")]
+use crate::feature::retry::policy::RetryPolicy;
+#[cfg(feature = "stream")]
+use crate::feature::executor::stream::{DeltaSink, PromptStream};
 
 pub struct PromptRetryExecutable<'a, C, S>
 where
@@ -11,7 +26,8 @@ where
     S: for<'de> Deserialize<'de>
 {
     prompt: &'a SendPromptVariant<'a, C>,
-    processor: Box<dyn Fn(CreateChatCompletionResponse, &mut C) -> Result<Option<S>, serde_json::Error> + Send + Sync + 'static>,
+    processor: Box<SendProcessor<'static, C, S>>,
+    response_format: Option<ResponseFormat>,
 }
 
 pub struct PromptRetryExecutableWithModel<'a, C, S>
@@ -20,7 +36,9 @@ where
     S: for<'de> Deserialize<'de>
 {
     prompt: PromptRetryExecutable<'a, C, S>,
-    models: Vec<&'a str>,
+    settings: Box<ExecutableSettings<'a, C>>,
+    #[cfg(feature = "stream")]
+    sink: Option<DeltaSink>, //set while streaming, so every retry attempt streams too
 }
 
 impl<'a, C, S> PromptRetryExecutable<'a, C, S>
@@ -28,13 +46,15 @@ where
     C: Context,
     S: for<'de> Deserialize<'de>
 {
-    pub fn get_processor(&self) -> &(dyn (Fn(CreateChatCompletionResponse, &mut C) -> Result<Option<S>, serde_json::Error>) + Send + Sync) {
+    pub fn get_processor(&self) -> &SendProcessor<'_, C, S> {
         &self.processor
     }
-    pub fn models(self, models: Vec<&'a str>) -> PromptRetryExecutableWithModel<'a, C, S> {
+    pub fn models(mut self, models: impl Into<ModelList<'a>>) -> PromptRetryExecutableWithModel<'a, C, S> {
         PromptRetryExecutableWithModel {
+            settings: Box::new(ExecutableSettings::with_models(models.into(), self.response_format.take())),
             prompt: self,
-            models,
+            #[cfg(feature = "stream")]
+            sink: None,
         }
     }
 }
@@ -44,59 +64,74 @@ where
     C: Context,
     S: for<'de> Deserialize<'de>
 {
-    pub fn model_count(&self) -> usize {
-        self.models.len()
+    pub(crate) fn capable_models(&self) -> Vec<usize> {
+        self.settings.capable_models()
     }
-    pub async fn execute_with_retry(self, context: &'a mut C,client: &'a Client<OpenAIConfig>, select_model: Option<usize>) -> RetryablePromptResult<'a, C, S>
+    settings_methods!();
+    pub async fn execute_with_retry(self, context: &'a mut C, backend: &'a dyn LlmBackend, select_model: Option<usize>) -> RetryablePromptResult<'a, C, S>
     where
         C: Send + Sync + 'static,
         S: Send + Sync + 'static
     {
-        if self.models.is_empty() {
-            return RetryablePromptResult::err((self, ModelNotSet, context, client, select_model));
-        }
-        let selected_model = select_model.unwrap_or(0);
-        let model = if let Some(&model) = self.models.get(selected_model) {
-            model
-        } else {
-            return RetryablePromptResult::err((self, InvalidModelSelection(selected_model), context, client, select_model))
-        };
-        let prompt_str = match self.prompt.prompt.prompt_str(context) {
-            Ok(Some(prompt_str)) => prompt_str,
-            Ok(None) => return RetryablePromptResult::ok(None),
-            Err(e) => return RetryablePromptResult::err((self, e.into(), context, client, select_model)),
-        };
-
-        let user_message =  match ChatCompletionRequestUserMessageArgs::default()
-            .content(&*prompt_str)
-            .build() {
-            Ok(user_message) => user_message,
-            Err(e) => return RetryablePromptResult::err((self, e.into(), context, client, select_model)),
-        };
-
-        let request = CreateChatCompletionRequestArgs::default()
-            .messages(vec![
-                user_message.into()
-            ])
-            .model(model)
-            .build();
-        let request = match request {
-            Ok(request) => request,
-            Err(e) => return RetryablePromptResult::err((self, e.into(), context, client, select_model)),
-        };
-
-        let response = client.chat()
-            .create(request)
-            .await;
-        let response = match response {
-            Ok(response) => response,
-            Err(e) => return RetryablePromptResult::err((self, e.into(), context, client, select_model)),
-        };
-        let deserialized_result = self.prompt.get_processor()(response, context);
-        match deserialized_result {
+        self.attempt(context, backend, select_model, None, StepUsage::default()).await
+    }
+    //Every attempt reads the history from `conversation`, only the one that succeeds is recorded in it
+    pub async fn execute_in_with_retry(self, context: &'a mut C, backend: &'a dyn LlmBackend, select_model: Option<usize>, conversation: &'a mut Conversation) -> RetryablePromptResult<'a, C, S>
+    where
+        C: Send + Sync + 'static,
+        S: Send + Sync + 'static
+    {
+        self.attempt(context, backend, select_model, Some(conversation), StepUsage::default()).await
+    }
+    //`usage` holds the requests of earlier attempts, the result adds this one's
+    pub(crate) async fn attempt(self, context: &'a mut C, backend: &'a dyn LlmBackend, select_model: Option<usize>, mut conversation: Option<&'a mut Conversation>, usage: StepUsage) -> RetryablePromptResult<'a, C, S>
+    where
+        C: Send + Sync + 'static,
+        S: Send + Sync + 'static
+    {
+        let usage = UsageRecorder::continuing(usage);
+        let result = execute_prompt(
+            self.prompt.prompt,
+            self.prompt.get_processor(),
+            &self.settings,
+            context,
+            backend,
+            ExecutionScope {
+                select_model,
+                conversation: conversation.as_deref_mut(),
+                usage: Some(&usage),
+                #[cfg(feature = "stream")]
+                sink: self.sink.as_ref(),
+                ..Default::default()
+            }
+        ).await;
+        let result = match result {
             Ok(result) => RetryablePromptResult::ok(result),
-            Err(e) => RetryablePromptResult::err((self, e.into(), context, client, select_model)),
-        }
+            Err(e) => {
+                let mut error = RetryableExecuteError::new(e, self, context, backend, select_model);
+                error.conversation = conversation;
+                RetryablePromptResult::err(error)
+            },
+        };
+        result.with_usage(usage.finish())
+    }
+    //Streams every attempt, a `StreamEvent::Reset` tells that the deltas so far were dropped for a retry
+    #[cfg(feature = "stream")]
+    pub fn execute_stream_with_retry(mut self, context: &'a mut C, backend: &'a dyn LlmBackend, select_model: Option<usize>, policy: impl Into<RetryPolicy>) -> PromptStream<impl Future<Output = Result<Option<S>, PromptExecutableError>> + 'a, S>
+    where
+        C: Send + Sync + 'static,
+        S: Send + Sync + 'static
+    {
+        let policy = policy.into();
+        let (sink, chunks) = DeltaSink::channel();
+        self.sink = Some(sink);
+        PromptStream::new(async move {
+            self.execute_with_retry(context, backend, select_model)
+                .await
+                .retry(policy)
+                .await
+                .into_result()
+        }, chunks)
     }
 }
 
@@ -107,11 +142,24 @@ where
     pub fn to_retry_executable<S, F>(&'a self, processor: F) -> PromptRetryExecutable<'a, C, S>
     where
         S: for<'de> Deserialize<'de>,
-        F: Fn(CreateChatCompletionResponse, &mut C) -> Result<Option<S>, serde_json::Error> + Send + Sync + 'static
+        F: Fn(ChatResponse, &mut C) -> Result<Option<S>, ProcessorError> + Send + Sync + 'static
     {
         PromptRetryExecutable {
             prompt: self,
             processor: Box::new(processor),
+            response_format: None,
+        }
+    }
+    #[cfg(feature = "schema")]
+    pub fn to_structured_retry_executable<S>(&'a self) -> PromptRetryExecutable<'a, C, S>
+    where
+        C: 'static,
+        S: DeserializeOwned + JsonSchema + 'static
+    {
+        PromptRetryExecutable {
+            prompt: self,
+            processor: Box::new(structured_output::<C, S>),
+            response_format: Some(ResponseFormat::json_schema_for::<S>()),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::feature::executor::backend::{ApiError, BackendError};
+use crate::feature::executor::prompt_result::{ProcessorError, PromptExecutableError};
+
+pub type ErrorClassifier = dyn Fn(&PromptExecutableError) -> Option<ErrorClass> + Send + Sync;
+
+//The kinds of failure a retry rule can be set for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    RateLimit, //429
+    ContextLength, //the prompt does not fit the model
+    CircuitOpen, //the model is deemed down by a circuit breaker
+    ServerError, //500, 502, 503, 504
+    Timeout, //an attempt ran past the step timeout
+    Transient, //transport, stream and malformed responses
+    InvalidOutput, //empty, invalid or undeserializable answers
+    Rejected, //refusals, content filter and truncated answers
+    Fatal,
+}
+impl ErrorClass {
+    pub fn of(error: &PromptExecutableError) -> ErrorClass {
+        match error {
+            PromptExecutableError::Backend(e) => match e {
+                BackendError::Transport(_) | BackendError::InvalidResponse(_) | BackendError::Stream(_) => ErrorClass::Transient,
+                BackendError::CircuitOpen { .. } => ErrorClass::CircuitOpen,
+                BackendError::Api(e) if e.matches_status(429) => ErrorClass::RateLimit,
+                BackendError::Api(e) if e.is_context_length_exceeded() => ErrorClass::ContextLength,
+                BackendError::Api(e) if [500, 502, 503, 504].into_iter().any(|status| e.matches_status(status)) => ErrorClass::ServerError,
+                _ => ErrorClass::Fatal,
+            },
+            PromptExecutableError::ContextWindowExceeded { .. } => ErrorClass::ContextLength,
+            PromptExecutableError::Processor(e) => match e {
+                ProcessorError::EmptyResponse | ProcessorError::Validation(_) | ProcessorError::Deserialize(_) => ErrorClass::InvalidOutput,
+                ProcessorError::Refusal(_) | ProcessorError::ContentFilter | ProcessorError::Truncated => ErrorClass::Rejected,
+                ProcessorError::Custom(_) => ErrorClass::Fatal,
+            },
+            #[cfg(feature = "timeout")]
+            PromptExecutableError::Timeout(_) => ErrorClass::Timeout,
+            _ => ErrorClass::Fatal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryRule {
+    GiveUp,
+    Immediately, //same model, without waiting
+    Backoff, //same model, after the backoff delay
+    SwitchModel, //next model at once, gives up with a single model
+    SwitchModelOrBackoff, //next model at once, backs off with a single model
+}
+
+pub(crate) struct RetryStep {
+    pub select_model: Option<usize>,
+    pub delay: Duration,
+}
+
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    initial_delay: Duration,
+    multiplier: f64,
+    max_delay: Duration,
+    jitter: f64, //share of each delay that may be randomly taken off
+    deadline: Option<Duration>, //counted from the first failure
+    rules: HashMap<ErrorClass, RetryRule>,
+    classifier: Option<Arc<ErrorClassifier>>,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(60),
+            jitter: 0.25,
+            deadline: None,
+            rules: HashMap::from([
+                (ErrorClass::RateLimit, RetryRule::SwitchModelOrBackoff),
+                (ErrorClass::ContextLength, RetryRule::SwitchModel),
+                (ErrorClass::CircuitOpen, RetryRule::SwitchModelOrBackoff),
+                (ErrorClass::ServerError, RetryRule::Backoff),
+                (ErrorClass::Timeout, RetryRule::Backoff),
+                (ErrorClass::Transient, RetryRule::Immediately),
+                (ErrorClass::InvalidOutput, RetryRule::Immediately),
+                (ErrorClass::Rejected, RetryRule::SwitchModel),
+                (ErrorClass::Fatal, RetryRule::GiveUp),
+            ]),
+            classifier: None,
+        }
+    }
+}
+impl From<usize> for RetryPolicy {
+    fn from(max_retries: usize) -> Self {
+        RetryPolicy::new().max_retries(max_retries)
+    }
+}
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+    pub fn initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+    pub fn rule(mut self, class: ErrorClass, rule: RetryRule) -> Self {
+        self.rules.insert(class, rule);
+        self
+    }
+    //Errors the classifier returns `None` for are classified by `ErrorClass::of`
+    pub fn classifier<F>(mut self, classifier: F) -> Self
+    where
+        F: Fn(&PromptExecutableError) -> Option<ErrorClass> + Send + Sync + 'static
+    {
+        self.classifier = Some(Arc::new(classifier));
+        self
+    }
+    pub fn get_max_retries(&self) -> usize {
+        self.max_retries
+    }
+    pub fn get_max_delay(&self) -> Duration {
+        self.max_delay
+    }
+    pub fn get_deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+    pub fn classify(&self, error: &PromptExecutableError) -> ErrorClass {
+        self.classifier
+            .as_ref()
+            .and_then(|classifier| classifier(error))
+            .unwrap_or_else(|| ErrorClass::of(error))
+    }
+    pub fn rule_for(&self, class: ErrorClass) -> RetryRule {
+        self.rules.get(&class).copied().unwrap_or(RetryRule::GiveUp)
+    }
+    //initial_delay * multiplier^attempt capped by max_delay, then up to `jitter` of it taken off
+    pub fn backoff_delay(&self, attempt: usize) -> Duration {
+        let exponent = attempt.min(i32::MAX as usize) as i32;
+        let secs = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        let delay = Duration::from_secs_f64(secs.min(self.max_delay.as_secs_f64()));
+        delay.mul_f64(1.0 - self.jitter * random_fraction())
+    }
+    //`None` when the error is not worth retrying
+    pub(crate) fn next_step(&self, error: &PromptExecutableError, attempt: usize, model_selected: Option<usize>, models: &[usize]) -> Option<RetryStep> {
+        //A wrong index is the caller's mistake, the first model is as good as any
+        if let PromptExecutableError::InvalidModelSelection(_) = error {
+            return Some(RetryStep { select_model: None, delay: Duration::ZERO });
+        }
+        let same_model = |delay| Some(RetryStep { select_model: model_selected, delay });
+        let next_model = || Some(RetryStep {
+            select_model: Some(next_model(model_selected, models)),
+            delay: Duration::ZERO,
+        });
+        let model_count = models.len();
+        match self.rule_for(self.classify(error)) {
+            RetryRule::GiveUp => None,
+            RetryRule::Immediately => same_model(Duration::ZERO),
+            RetryRule::Backoff => same_model(self.wait(error, attempt)),
+            RetryRule::SwitchModel if model_count > 1 => next_model(),
+            RetryRule::SwitchModel => None,
+            RetryRule::SwitchModelOrBackoff if model_count > 1 => next_model(),
+            RetryRule::SwitchModelOrBackoff => same_model(self.wait(error, attempt)),
+        }
+    }
+    //The server knows best how long to wait, as long as it stays under max_delay
+    fn wait(&self, error: &PromptExecutableError, attempt: usize) -> Duration {
+        match error {
+            PromptExecutableError::Backend(BackendError::Api(ApiError { retry_after: Some(retry_after), .. }))
+            | PromptExecutableError::Backend(BackendError::CircuitOpen { retry_after, .. }) => {
+                (*retry_after).min(self.max_delay)
+            },
+            _ => self.backoff_delay(attempt),
+        }
+    }
+}
+
+//The model after the one that served `model_selected`, which is the first capable one from there
+fn next_model(model_selected: Option<usize>, models: &[usize]) -> usize {
+    let after = |from: usize| models.iter().copied().find(|&model| model >= from).unwrap_or(models[0]);
+    let served = after(model_selected.unwrap_or(0));
+    after(served + 1)
+}
+
+//A fresh RandomState is randomly keyed, good enough for spreading retries without a rand dependency
+fn random_fraction() -> f64 {
+    (RandomState::new().hash_one(0u8) >> 11) as f64 / (1u64 << 53) as f64
+}
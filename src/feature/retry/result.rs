@@ -1,9 +1,10 @@
-use std::pin::Pin;
 use serde::Deserialize;
-use async_openai::Client;
-use async_openai::config::OpenAIConfig;
+use crate::feature::executor::backend::LlmBackend;
+use crate::feature::executor::conversation::Conversation;
+use crate::feature::executor::usage::StepUsage;
+use crate::feature::send::result::SendPromptResult;
 use crate::feature::retry::executable::PromptRetryExecutableWithModel;
-use crate::prelude::{Context, PromptExecutableError, RetryStrategy};
+use crate::prelude::{Context, PromptExecutableError, RetryPolicy, RetryStrategy};
 
 pub struct RetryableExecuteError<'a, C, S>
 where
@@ -13,8 +14,10 @@ where
     pub error: PromptExecutableError,
     pub origin: PromptRetryExecutableWithModel<'a, C, S>,
     pub context: &'a mut C,
-    pub client: &'a Client<OpenAIConfig>,
+    pub backend: &'a dyn LlmBackend,
     pub model_selected: Option<usize>,
+    pub conversation: Option<&'a mut Conversation>, //retries read and record the same one
+    pub usage: StepUsage, //every request of the attempts so far
 }
 
 impl<'a, C, S> RetryableExecuteError<'a, C, S>
@@ -26,37 +29,37 @@ where
         error: PromptExecutableError,
         origin: PromptRetryExecutableWithModel<'a, C, S>,
         context: &'a mut C,
-        client: &'a Client<OpenAIConfig>,
+        backend: &'a dyn LlmBackend,
         model_selected: Option<usize>
     ) -> Self {
-        Self { error, origin, context, client, model_selected}
+        Self { error, origin, context, backend, model_selected, conversation: None, usage: StepUsage::default() }
     }
-    pub async fn retry(self, retry_times: usize) -> Result<Option<S>, PromptExecutableError> {
-        RetryStrategy::default_retry(self, retry_times).await
+    pub async fn retry(self, policy: impl Into<RetryPolicy>) -> SendPromptResult<'a, S> {
+        RetryStrategy::retry(self, &policy.into()).await
     }
 }
 
-impl<'a, C, S> From<(PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a Client<OpenAIConfig>, Option<usize>)> for RetryableExecuteError<'a, C, S>
+impl<'a, C, S> From<(PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a dyn LlmBackend, Option<usize>)> for RetryableExecuteError<'a, C, S>
 where
     C: Context + Send + Sync,
     S: for<'de> Deserialize<'de> + Send + Sync + 'static
 {
-    fn from(value: (PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a Client<OpenAIConfig>, Option<usize>)) -> Self {
+    fn from(value: (PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a dyn LlmBackend, Option<usize>)) -> Self {
         Self::new(value.1, value.0, value.2, value.3, value.4)
     }
 }
 
-impl<'a, C, S> From<(PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a Client<OpenAIConfig>, Option<usize>)> for RetryablePromptResult<'a, C, S>
+impl<'a, C, S> From<(PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a dyn LlmBackend, Option<usize>)> for RetryablePromptResult<'a, C, S>
 where
     C: Context + Send + Sync,
     S: for<'de> Deserialize<'de> + Send + Sync + 'static
 {
-    fn from(value: (PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a Client<OpenAIConfig>, Option<usize>)) -> Self {
+    fn from(value: (PromptRetryExecutableWithModel<'a, C, S>, PromptExecutableError, &'a mut C, &'a dyn LlmBackend, Option<usize>)) -> Self {
         Self::err(value)
     }
 }
 
-pub struct RetryablePromptResult<'a, C, S>(Result<Option<S>, RetryableExecuteError<'a, C, S>>)
+pub struct RetryablePromptResult<'a, C, S>(Result<Option<S>, RetryableExecuteError<'a, C, S>>, StepUsage)
 where
     C: Context + Send + Sync + 'static,
     S: for<'de> Deserialize<'de> + Send + Sync;
@@ -66,11 +69,13 @@ where
     C: Context + Send + Sync,
     S: for<'de> Deserialize<'de> + Send + Sync + 'static,
 {
-    pub async fn retry(self, retry_times: usize)  -> Result<Option<S>, PromptExecutableError> {
+    //Plain numbers work too: `retry(3)` is the default policy with 3 retries.
+    //The result's usage counts the requests of every attempt.
+    pub async fn retry(self, policy: impl Into<RetryPolicy>) -> SendPromptResult<'a, S> {
         match self.0 {
-            Ok(s) => Ok(s),
+            Ok(s) => SendPromptResult::ok(s).with_usage(self.1),
             Err(e) => {
-                e.retry(retry_times).await
+                e.retry(policy).await
             }
         }
     }
@@ -80,6 +85,20 @@ where
     pub fn is_err(&self) -> bool {
         self.0.is_err()
     }
+    //Tokens, latency and cost of every request made so far, failed ones included
+    pub fn usage(&self) -> &StepUsage {
+        match &self.0 {
+            Ok(_) => &self.1,
+            Err(e) => &e.usage,
+        }
+    }
+    pub(crate) fn with_usage(mut self, usage: StepUsage) -> Self {
+        match &mut self.0 {
+            Ok(_) => self.1 = usage,
+            Err(e) => e.usage = usage,
+        }
+        self
+    }
     pub fn unwrap(self) -> Option<S> {
         match self.0 {
             Ok(s) => s,
@@ -88,7 +107,7 @@ where
     }
     pub fn unwrap_err(self) -> RetryableExecuteError<'a, C, S> {
         match self.0 {
-            Ok(s) => panic!("RetryablePromptResult is ok."),
+            Ok(_) => panic!("RetryablePromptResult is ok."),
             Err(e) => e
         }
     }
@@ -100,10 +119,10 @@ where
     S: for<'de> Deserialize<'de> + Send + Sync
 {
     pub fn ok(value: impl Into<Option<S>>) -> Self {
-        Self(Ok(value.into()))
+        Self(Ok(value.into()), StepUsage::default())
     }
     pub fn err(value: impl Into<RetryableExecuteError<'a, C, S>>) -> Self {
-        Self(Err(value.into()))
+        Self(Err(value.into()), StepUsage::default())
     }
 }
 
@@ -113,6 +132,6 @@ where
     S: for<'de> Deserialize<'de> + Send + Sync
 {
     fn from(value: RetryableExecuteError<'a, C, S>) -> Self {
-        Self(Err(value))
+        Self(Err(value), StepUsage::default())
     }
 }
\ No newline at end of file
@@ -0,0 +1,44 @@
+use tokio::time::Instant;
+use crate::feature::executor::prompt_result::PromptExecutableError;
+use crate::feature::retry::policy::RetryPolicy;
+
+//Retry bookkeeping of one execution, so any executable can loop over its own attempts
+pub(crate) struct Retrier<'p> {
+    policy: &'p RetryPolicy,
+    models: Vec<usize>, //the ones able to serve the request, rotations skip the others
+    model_selected: Option<usize>,
+    retries: usize,
+    first_failure: Option<Instant>, //the deadline counts from here
+}
+impl<'p> Retrier<'p> {
+    pub(crate) fn new(policy: &'p RetryPolicy, models: Vec<usize>, select_model: Option<usize>) -> Self {
+        Retrier {
+            policy,
+            models,
+            model_selected: select_model,
+            retries: 0,
+            first_failure: None,
+        }
+    }
+    pub(crate) fn select_model(&self) -> Option<usize> {
+        self.model_selected
+    }
+    //Waits as the policy says and moves to the model of the next attempt,
+    //or hands back the error to return: as is if not retryable, as `RetryFail` once out of retries or time
+    pub(crate) async fn recover(&mut self, error: PromptExecutableError) -> Result<(), PromptExecutableError> {
+        let first_failure = *self.first_failure.get_or_insert_with(Instant::now);
+        let Some(step) = self.policy.next_step(&error, self.retries, self.model_selected, &self.models) else {
+            return Err(error);
+        };
+        if self.retries >= self.policy.get_max_retries()
+            || self.policy.get_deadline().is_some_and(|deadline| first_failure.elapsed() + step.delay > deadline) {
+            return Err(PromptExecutableError::RetryFail(Box::new(error)));
+        }
+        if !step.delay.is_zero() {
+            tokio::time::sleep(step.delay).await;
+        }
+        self.retries += 1;
+        self.model_selected = step.select_model;
+        Ok(())
+    }
+}
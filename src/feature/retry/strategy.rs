@@ -0,0 +1,41 @@
+use serde::Deserialize;
+use crate::feature::send::result::SendPromptResult;
+use crate::feature::retry::policy::RetryPolicy;
+use crate::feature::retry::result::RetryableExecuteError;
+use crate::feature::retry::retrier::Retrier;
+use crate::prelude::Context;
+
+pub struct RetryStrategy;
+impl RetryStrategy {
+    //Errors the policy won't retry are returned as is, running out of retries or time gives `RetryFail`.
+    //The usage counts the requests of every attempt, the ones before `retryable_error` included.
+    pub async fn retry<'a, C, S>(retryable_error: RetryableExecuteError<'a, C, S>, policy: &RetryPolicy) -> SendPromptResult<'a, S>
+    where
+        C: Context + Send + Sync + 'static,
+        S: for<'de> Deserialize<'de> + Send + Sync + 'static
+    {
+        let mut retrier = Retrier::new(policy, retryable_error.origin.capable_models(), retryable_error.model_selected);
+        let mut error_retry = retryable_error;
+        loop {
+            let RetryableExecuteError {
+                origin,
+                error,
+                context,
+                backend,
+                conversation,
+                usage,
+                ..
+            } = error_retry;
+            if let Err(error) = retrier.recover(error).await {
+                return SendPromptResult::err(error).with_usage(usage);
+            }
+            let retry_result = origin.attempt(context, backend, retrier.select_model(), conversation, usage).await;
+            if retry_result.is_err() {
+                error_retry = retry_result.unwrap_err();
+            } else {
+                let usage = retry_result.usage().clone();
+                return SendPromptResult::ok(retry_result.unwrap()).with_usage(usage);
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 
 pub mod flow;
 pub mod control;
-#[cfg(feature = "async_oai")]
+#[cfg(feature = "executable")]
 pub mod result;
\ No newline at end of file
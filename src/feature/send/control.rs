@@ -1,8 +1,10 @@
 use std::borrow::Cow;
-use crate::prelude::{IfPrompt, PromptTemplate, PromptVariant};
+use std::collections::{BTreeSet, HashMap};
+use crate::prelude::PromptTemplate;
 use crate::prompt::context::Context;
 use crate::prompt::error::{IfPromptBuilderError, PromptError};
 use crate::prompt::naive::{Prompt};
+use crate::prompt::role::{ComposedParts, PromptMessage, Role, RolePart};
 
 pub struct SendIfPrompt<'a, C>
 where
@@ -16,10 +18,10 @@ impl<'a, C> SendIfPrompt<'a, C>
 where
     C: Context
 {
-    pub fn get_then(&self) -> &SendPromptVariant<C> {
+    pub fn get_then(&self) -> &SendPromptVariant<'_, C> {
         &self.then
     }
-    pub fn get_otherwise(&self) -> Option<&SendPromptVariant<C>> {
+    pub fn get_otherwise(&self) -> Option<&SendPromptVariant<'_, C>> {
         self.otherwise.as_ref()
     }
     pub fn get_condition(&self) -> &(dyn Fn(&C) -> bool + Send + Sync + 'a) {
@@ -30,16 +32,45 @@ impl<C> Prompt<C> for SendIfPrompt<'_, C>
 where
     C: Context
 {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError> {
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt_str_with(context, &HashMap::new())
+    }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt_messages_with(context, &HashMap::new())
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
+        if (self.condition)(context) {
+            self.then.prompt_str_with(context, overrides)
+        } else if let Some(otherwise) = self.otherwise.as_ref() {
+            otherwise.prompt_str_with(context, overrides)
+        } else {
+            Ok(None)
+        }
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
         if (self.condition)(context) {
-            self.then.prompt_str(context)
+            self.then.prompt_messages_with(context, overrides)
+        } else if let Some(otherwise) = self.otherwise.as_ref() {
+            otherwise.prompt_messages_with(context, overrides)
         } else {
-            if let Some(otherwise) = self.otherwise.as_ref() {
-                otherwise.prompt_str(context)
-            }
-            else {
-                Ok(None)
-            }
+            Ok(Vec::new())
+        }
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        let mut variables = self.then.variables();
+        variables.extend(self.otherwise.iter().flat_map(|otherwise| otherwise.variables()));
+        variables
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        let mut variables = self.then.required_variables();
+        variables.extend(self.otherwise.iter().flat_map(|otherwise| otherwise.required_variables()));
+        variables
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        if (self.condition)(context) {
+            self.then.missing_variables(context)
+        } else {
+            self.otherwise.as_ref().map(|otherwise| otherwise.missing_variables(context)).unwrap_or_default()
         }
     }
 }
@@ -54,7 +85,7 @@ impl<'a, C> SendLoopPrompt<'a, C>
 where
     C: Context + 'a,
 {
-    pub fn get_prompt(&self) -> &SendPromptVariant<C> {
+    pub fn get_prompt(&self) -> &SendPromptVariant<'_, C> {
         &self.prompt
     }
     pub fn get_condition(&self) -> &(dyn Fn(&C) -> bool + Send + Sync + 'a) {
@@ -65,9 +96,31 @@ impl<'a, C> Prompt<C> for SendLoopPrompt<'a, C>
 where
     C: Context,
 {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError> {
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
         self.prompt.prompt_str(context)
     }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt.prompt_messages(context)
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt.prompt_str_with(context, overrides)
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt.prompt_messages_with(context, overrides)
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        self.prompt.variables()
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        self.prompt.required_variables()
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        if (self.condition)(context) {
+            self.prompt.missing_variables(context)
+        } else {
+            Vec::new()
+        }
+    }
 }
 
 //Builders
@@ -161,6 +214,9 @@ where
     }
 }
 
+pub type SendRolePrompt<'a, C> = RolePart<SendPromptVariant<'a, C>>;
+pub type SendComposedPrompt<'a, C> = ComposedParts<SendPromptVariant<'a, C>>;
+
 pub enum SendPromptVariant<'a, C>
 where
     C: Context
@@ -169,6 +225,7 @@ where
     Template(PromptTemplate),
     If(Box<SendIfPrompt<'a, C>>),
     Loop(Box<SendLoopPrompt<'a, C>>),
+    Composed(Box<SendComposedPrompt<'a, C>>),
 }
 impl<'a, C: Context> SendPromptVariant<'a, C> {
     pub fn naive(s: Cow<'a, str>) -> Self {
@@ -183,6 +240,12 @@ impl<'a, C: Context> SendPromptVariant<'a, C> {
     pub fn template(t: PromptTemplate) -> Self {
         SendPromptVariant::Template(t)
     }
+    pub fn composed(p: SendComposedPrompt<'a, C>) -> Self {
+        SendPromptVariant::Composed(Box::new(p))
+    }
+    pub fn with_role(self, role: Role) -> Self {
+        SendPromptVariant::composed(RolePart::new(role, self).into())
+    }
 }
 impl<'a, C: Context> From<String> for SendPromptVariant<'a, C> {
     fn from(s: String) -> Self {
@@ -214,13 +277,68 @@ impl<'a, C: Context> From<SendLoopPrompt<'a, C>> for SendPromptVariant<'a, C> {
         SendPromptVariant::loop_prompt(p)
     }
 }
+impl<'a, C: Context> From<SendComposedPrompt<'a, C>> for SendPromptVariant<'a, C> {
+    fn from(p: SendComposedPrompt<'a, C>) -> Self {
+        SendPromptVariant::composed(p)
+    }
+}
+impl<'a, C: Context> From<RolePart<SendPromptVariant<'a, C>>> for SendPromptVariant<'a, C> {
+    fn from(p: RolePart<SendPromptVariant<'a, C>>) -> Self {
+        SendPromptVariant::composed(p.into())
+    }
+}
 impl<C: Context> Prompt<C> for SendPromptVariant<'_, C> {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError> {
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt_str_with(context, &HashMap::new())
+    }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt_messages_with(context, &HashMap::new())
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
         match self {
             SendPromptVariant::Naive(s) => Ok(Some(Cow::Borrowed(s))), //Is this right?
-            SendPromptVariant::Template(p) => p.prompt_str(context),
-            SendPromptVariant::If(p) => p.prompt_str(context),
-            SendPromptVariant::Loop(p) => p.prompt_str(context),
+            SendPromptVariant::Template(p) => p.prompt_str_with(context, overrides),
+            SendPromptVariant::If(p) => p.prompt_str_with(context, overrides),
+            SendPromptVariant::Loop(p) => p.prompt_str_with(context, overrides),
+            SendPromptVariant::Composed(p) => p.prompt_str_with(context, overrides),
+        }
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        match self {
+            SendPromptVariant::If(p) => p.prompt_messages_with(context, overrides),
+            SendPromptVariant::Loop(p) => p.prompt_messages_with(context, overrides),
+            SendPromptVariant::Composed(p) => p.prompt_messages_with(context, overrides),
+            _ => Ok(self.prompt_str_with(context, overrides)?
+                .map(|content| PromptMessage::new(Role::User, content))
+                .into_iter()
+                .collect()),
+        }
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        match self {
+            SendPromptVariant::Naive(_) => BTreeSet::new(),
+            SendPromptVariant::Template(p) => Prompt::<C>::variables(p),
+            SendPromptVariant::If(p) => p.variables(),
+            SendPromptVariant::Loop(p) => p.variables(),
+            SendPromptVariant::Composed(p) => p.variables(),
+        }
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        match self {
+            SendPromptVariant::Naive(_) => BTreeSet::new(),
+            SendPromptVariant::Template(p) => Prompt::<C>::required_variables(p),
+            SendPromptVariant::If(p) => p.required_variables(),
+            SendPromptVariant::Loop(p) => p.required_variables(),
+            SendPromptVariant::Composed(p) => p.required_variables(),
+        }
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        match self {
+            SendPromptVariant::Naive(_) => Vec::new(),
+            SendPromptVariant::Template(p) => p.missing_variables(context),
+            SendPromptVariant::If(p) => p.missing_variables(context),
+            SendPromptVariant::Loop(p) => p.missing_variables(context),
+            SendPromptVariant::Composed(p) => p.missing_variables(context),
         }
     }
 }
\ No newline at end of file
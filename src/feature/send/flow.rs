@@ -1,7 +1,9 @@
+use std::collections::BTreeSet;
 use std::iter::Peekable;
 use crate::prelude::SendPromptVariant;
 use crate::prompt::context::Context;
-use crate::prompt::naive::{PromptVariant};
+use crate::prompt::error::PromptError;
+use crate::prompt::naive::{check_missing, merge_missing, undeclared, Prompt};
 
 #[derive(Default)]
 pub struct SendPromptChain<'a, C: Context> {
@@ -16,6 +18,25 @@ impl<'a, C: Context> SendPromptChain<'a, C> {
     pub fn push(&mut self, prompt: impl Into<SendPromptVariant<'a, C>>) {
         self.prompts.push(prompt.into());
     }
+    pub fn variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.variables()).collect()
+    }
+    pub fn required_variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.required_variables()).collect()
+    }
+    //Every step is checked against the context as it is now, so values earlier steps would set count as missing
+    pub fn missing_variables(&self, context: &C) -> Vec<String> {
+        self.prompts.iter().fold(Vec::new(), |mut missing, prompt| {
+            merge_missing(&mut missing, prompt.missing_variables(context));
+            missing
+        })
+    }
+    pub fn validate(&self, context: &C) -> Result<(), PromptError> {
+        check_missing(self.missing_variables(context))
+    }
+    pub fn validate_type(&self) -> Result<(), PromptError> {
+        check_missing(undeclared::<C>(self.required_variables()))
+    }
     pub fn flow(&'a self) -> SendFlow<'a, C> {
         SendFlow {
             prompts: self.prompts.iter().peekable()
@@ -33,20 +54,21 @@ impl<'a, C> SendFlow<'a, C>
 where
     C: Context,
 {
-    pub fn next_with(&mut self, context: &C) -> Option<&SendPromptVariant<C>> {
+    pub fn next_with(&mut self, context: &C) -> Option<&SendPromptVariant<'_, C>> {
         loop {
             let mut final_break = false;
+            let mut in_loop = false;
             let res = self.prompts.peek().and_then(|&prompt| {
                 let mut cur_prompt = prompt;
                 loop {
                     match cur_prompt {
-                        SendPromptVariant::Naive(_) | SendPromptVariant::Template(_)=> {
+                        SendPromptVariant::Naive(_) | SendPromptVariant::Template(_) | SendPromptVariant::Composed(_) => {
                             final_break = true;
                             break Some(cur_prompt)
                         },
                         SendPromptVariant::If(if_prompt) => {
                             if if_prompt.get_condition()(context) {
-                                cur_prompt = (if_prompt.get_then())
+                                cur_prompt = if_prompt.get_then()
                             } else {
                                 cur_prompt = if let Some(otherwise) = if_prompt.get_otherwise() {
                                     otherwise
@@ -57,6 +79,7 @@ where
                         },
                         SendPromptVariant::Loop(loop_prompt) => {
                             if loop_prompt.get_condition()(context) {
+                                in_loop = true;
                                 cur_prompt = loop_prompt.get_prompt();
                             } else {
                                 break None
@@ -66,13 +89,14 @@ where
                 }
             });
             if final_break {
-                self.prompts.next();
+                if !in_loop { //NOTE: a loop stays at the front until its condition fails
+                    self.prompts.next();
+                }
                 break res
             }
-            if res.is_none() {
-                if self.prompts.next().is_none() {
-                    break None
-                }
+            //NOTE: an iteration that yields nothing ends the loop, the context can't change before the next check
+            if res.is_none() && self.prompts.next().is_none() {
+                break None
             }
         }
     }
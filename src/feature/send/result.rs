@@ -1,8 +1,9 @@
 use std::marker::PhantomData;
 use serde::Deserialize;
+use crate::feature::executor::usage::StepUsage;
 use crate::prelude::PromptExecutableError;
 
-pub struct SendPromptResult<'a, S>(Result<Option<S>, PromptExecutableError>, PhantomData<&'a ()>)
+pub struct SendPromptResult<'a, S>(Result<Option<S>, PromptExecutableError>, StepUsage, PhantomData<&'a ()>)
 where
     S: Deserialize<'a> + Send + Sync + 'a;
 
@@ -16,6 +17,14 @@ where
     pub fn is_err(&self) -> bool {
         self.0.is_err()
     }
+    //Tokens, latency and cost of every request the execution made, failed ones included
+    pub fn usage(&self) -> &StepUsage {
+        &self.1
+    }
+    pub(crate) fn with_usage(mut self, usage: StepUsage) -> Self {
+        self.1 = usage;
+        self
+    }
     pub fn unwrap(self) -> Option<S> {
         match self.0 {
             Ok(s) => s,
@@ -24,10 +33,13 @@ where
     }
     pub fn unwrap_err(self) -> PromptExecutableError {
         match self.0 {
-            Ok(s) => panic!("PromptResult is ok."),
+            Ok(_) => panic!("PromptResult is ok."),
             Err(e) => e
         }
     }
+    pub fn into_result(self) -> Result<Option<S>, PromptExecutableError> {
+        self.0
+    }
 }
 
 impl<'a, S> From<Option<S>> for SendPromptResult<'a, S>
@@ -35,7 +47,7 @@ where
     S: Deserialize<'a> + Send + Sync + 'a
 {
     fn from(value: Option<S>) -> Self {
-        Self(Ok(value), PhantomData)
+        Self(Ok(value), StepUsage::default(), PhantomData)
     }
 }
 
@@ -45,9 +57,9 @@ where
     S: Deserialize<'a> + Send + Sync + 'a
 {
     pub fn ok(value: impl Into<Option<S>>) -> Self {
-        Self(Ok(value.into()), PhantomData)
+        Self(Ok(value.into()), StepUsage::default(), PhantomData)
     }
     pub fn err(value: impl Into<PromptExecutableError>) -> Self {
-        Self(Err(value.into()), PhantomData)
+        Self(Err(value.into()), StepUsage::default(), PhantomData)
     }
 }
\ No newline at end of file
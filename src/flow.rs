@@ -1,6 +1,8 @@
+use std::collections::BTreeSet;
 use std::iter::Peekable;
 use crate::prompt::context::Context;
-use crate::prompt::naive::{PromptVariant};
+use crate::prompt::error::PromptError;
+use crate::prompt::naive::{check_missing, merge_missing, undeclared, Prompt, PromptVariant};
 
 #[derive(Default)]
 pub struct PromptChain<'a, C: Context> {
@@ -15,6 +17,25 @@ impl<'a, C: Context> PromptChain<'a, C> {
     pub fn push(&mut self, prompt: impl Into<PromptVariant<'a, C>>) {
         self.prompts.push(prompt.into());
     }
+    pub fn variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.variables()).collect()
+    }
+    pub fn required_variables(&self) -> BTreeSet<&str> {
+        self.prompts.iter().flat_map(|prompt| prompt.required_variables()).collect()
+    }
+    //Every step is checked against the context as it is now, so values earlier steps would set count as missing
+    pub fn missing_variables(&self, context: &C) -> Vec<String> {
+        self.prompts.iter().fold(Vec::new(), |mut missing, prompt| {
+            merge_missing(&mut missing, prompt.missing_variables(context));
+            missing
+        })
+    }
+    pub fn validate(&self, context: &C) -> Result<(), PromptError> {
+        check_missing(self.missing_variables(context))
+    }
+    pub fn validate_type(&self) -> Result<(), PromptError> {
+        check_missing(undeclared::<C>(self.required_variables()))
+    }
     pub fn flow(&'a self) -> Flow<'a, C> {
         Flow {
             prompts: self.prompts.iter().peekable()
@@ -32,20 +53,21 @@ impl<'a, C> Flow<'a, C>
 where
     C: Context,
 {
-    pub fn next_with(&mut self, context: &C) -> Option<&PromptVariant<C>> {
+    pub fn next_with(&mut self, context: &C) -> Option<&PromptVariant<'_, C>> {
         loop {
             let mut final_break = false;
+            let mut in_loop = false;
             let res = self.prompts.peek().and_then(|&prompt| {
                 let mut cur_prompt = prompt;
                 loop {
                     match cur_prompt {
-                        PromptVariant::Naive(_) | PromptVariant::Template(_)=> {
+                        PromptVariant::Naive(_) | PromptVariant::Template(_) | PromptVariant::Composed(_) => {
                             final_break = true;
                             break Some(cur_prompt)
                         },
                         PromptVariant::If(if_prompt) => {
                             if if_prompt.get_condition()(context) {
-                                cur_prompt = (if_prompt.get_then())
+                                cur_prompt = if_prompt.get_then()
                             } else {
                                 cur_prompt = if let Some(otherwise) = if_prompt.get_otherwise() {
                                     otherwise
@@ -56,6 +78,7 @@ where
                         },
                         PromptVariant::Loop(loop_prompt) => {
                             if loop_prompt.get_condition()(context) {
+                                in_loop = true;
                                 cur_prompt = loop_prompt.get_prompt();
                             } else {
                                 break None
@@ -65,13 +88,14 @@ where
                 }
             });
             if final_break {
-                self.prompts.next();
+                if !in_loop { //NOTE: a loop stays at the front until its condition fails
+                    self.prompts.next();
+                }
                 break res
             }
-            if res.is_none() {
-               if self.prompts.next().is_none() {
-                   break None
-               }
+            //NOTE: an iteration that yields nothing ends the loop, the context can't change before the next check
+            if res.is_none() && self.prompts.next().is_none() {
+                break None
             }
         }
     }
@@ -5,44 +5,74 @@ pub mod prelude {
     pub use crate::prompt::control::{IfPrompt, IfPromptBuilder, LoopPrompt, LoopPromptBuilder};
     pub use crate::prompt::naive::{Prompt, PromptVariant};
     pub use crate::prompt::template::PromptTemplate;
-    pub use crate::prompt::error::PromptError;
+    pub use crate::prompt::filter::{FilterRegistry, TemplateFilter};
+    pub use crate::prompt::error::{PromptError, PromptTemplateError, TemplateErrorKind};
     pub use crate::prompt::context::Context;
-    #[cfg(feature = "async_oai")]
-    pub use crate::feature::async_openai::prompt_result::{PromptExecutableError, PromptResult};
-    #[cfg(feature = "async_oai")]
-    pub use crate::feature::async_openai::executable::{PromptExecutableWithModel,PromptExecutable};
-    #[cfg(feature = "async_oai")]
-    pub use crate::feature::async_openai::executable_flow::{ExecutablePromptVariant, ExecutableIfPrompt, ExecutableFlow, ExecutablePromptChain, ExecutableLoopPrompt, ExecutableIfPromptBuilder, ExecutableLoopPromptBuilder};
-    #[cfg(feature = "retry")]
+    pub use crate::prompt::value::TemplateValue;
+    pub use crate::prompt::role::{Role, PromptMessage, RolePart, ComposedParts, RolePrompt, ComposedPrompt};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::backend::{LlmBackend, BackendFuture, BackendError, ApiError, ChatRequest, ChatResponse, ChatMessage, ChatChoice, FinishReason, GenerationParams, ResponseFormat, TokenUsage, ToolCall, ToolDefinition};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::tool::Tool;
+    #[cfg(feature = "stream")]
+    pub use crate::feature::executor::stream::{ChatDelta, ChatDeltaStream, PromptStream, ResponseAssembler, StreamEvent, ToolCallDelta};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::structured::structured_output;
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::processor;
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::model::{Fallback, ModelCost, ModelList, ModelSpec};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::tokenizer::{HeuristicTokenizer, TokenBudget, Tokenizer, Truncation};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::usage::{CallUsage, ChainUsage, PriceTable, StepUsage};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::conversation::{Conversation, HistoryMode, HistoryWindow};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::prompt_result::{ProcessorError, PromptExecutableError, PromptResult};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::executable::{PromptExecutableWithModel,PromptExecutable};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::executable_flow::{ExecutablePromptVariant, ExecutableIfPrompt, ExecutableFlow, ExecutablePromptChain, ExecutableLoopPrompt, ExecutableIfPromptBuilder, ExecutableLoopPromptBuilder};
+    #[cfg(all(feature = "executable", any(test, feature = "mock")))]
+    pub use crate::feature::executor::mock::ScriptedBackend;
+    #[cfg(all(feature = "retry", feature = "send"))]
     pub use crate::feature::retry::RetryStrategy;
+    #[cfg(feature = "batch")]
+    pub use crate::feature::executor::batch::{BatchExecution, BatchItem, BatchProgress};
+    #[cfg(feature = "timeout")]
+    pub use crate::feature::executor::interrupt::CancellationToken;
+    #[cfg(feature = "rate_limit")]
+    pub use crate::feature::rate_limit::{RateLimitedBackend, RateLimiter, RateLimits};
     #[cfg(feature = "retry")]
+    pub use crate::feature::retry::{CircuitBreaker, CircuitBreakerBackend, CircuitState, ErrorClass, RetryPolicy, RetryRule};
+    #[cfg(all(feature = "retry", feature = "send"))]
     pub use crate::feature::retry::result::RetryableExecuteError;
-    #[cfg(feature = "retry")]
+    #[cfg(all(feature = "retry", feature = "send"))]
     pub use crate::feature::retry::result::RetryablePromptResult;
     #[cfg(feature = "send")]
     pub use crate::feature::send::{control::*, flow::*};
     #[cfg(feature = "send")]
-    #[cfg(feature = "async_oai")]
+    #[cfg(feature = "executable")]
     pub use crate::feature::send::result::*;
     #[cfg(feature = "send")]
-    #[cfg(feature = "async_oai")]
-    pub use crate::feature::async_openai::executable::{SendPromptExecutable, SendPromptExecutableWithModel};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::executable::{SendPromptExecutable, SendPromptExecutableWithModel};
     #[cfg(feature = "send")]
-    #[cfg(feature = "async_oai")]
-    pub use crate::feature::async_openai::executable_flow::{SendExecutableFlow,SendExecutableIfPrompt,SendExecutableLoopPrompt,SendExecutablePromptChain,SendExecutablePromptVariant, SendExecutableIfPromptBuilder, SendExecutableLoopPromptBuilder};
+    #[cfg(feature = "executable")]
+    pub use crate::feature::executor::executable_flow::{SendExecutableFlow,SendExecutableIfPrompt,SendExecutableLoopPrompt,SendExecutablePromptChain,SendExecutablePromptVariant, SendExecutableIfPromptBuilder, SendExecutableLoopPromptBuilder};
 }
 
 pub mod feature;
 
 #[cfg(test)]
 mod tests {
-    use std::borrow::Cow;
-    #[cfg(feature = "async_oai")]
-    use async_openai::Client;
-    #[cfg(feature = "async_oai")]
-    use async_openai::config::OpenAIConfig;
+    #[cfg(feature = "executable")]
+    use std::time::Duration;
+    use std::collections::HashMap;
     use crate::flow::PromptChain;
     use crate::prelude::*;
+    use crate::prompt::context::DefaultContext;
 
     pub struct MyContext {
         pub name: String,
@@ -67,6 +97,9 @@ mod tests {
                 _ => None
             }
         }
+        fn template_var_names() -> Vec<&'static str> {
+            vec!["name", "a", "age"]
+        }
     }
 
 
@@ -147,6 +180,34 @@ mod tests {
             my_context.a += 1;
         }
     }
+    #[test]
+    fn loop_ends_on_empty_iteration() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 2,
+            age: "18".to_string()
+        };
+        let mut chain = PromptChain::<MyContext>::new();
+        chain.push(
+            LoopPromptBuilder::new()
+                .prompt(
+                    IfPromptBuilder::new()
+                        .then("Even")
+                        .condition(|my_context: &MyContext| my_context.a % 2 == 0)
+                        .build().unwrap()
+                )
+                .condition(|my_context: &MyContext| my_context.a < 5)
+                .build().unwrap()
+        );
+        chain.push("After the loop");
+        let mut flow = chain.flow();
+        let mut sent = Vec::new();
+        while let Some(prompt) = flow.next_with(&my_context) {
+            sent.push(prompt.prompt_str(&my_context).unwrap().unwrap().into_owned());
+            my_context.a += 1;
+        }
+        assert_eq!(sent, vec!["Even", "After the loop"]);
+    }
     #[cfg(feature = "send")]
     #[test]
     fn send_chain() {
@@ -225,7 +286,183 @@ mod tests {
             my_context.a += 1;
         }
     }
-    #[cfg(feature = "async_oai")]
+    fn parse_error(template: &str) -> Option<TemplateErrorKind> {
+        PromptTemplate::new(template).err().map(|error| error.kind().clone())
+    }
+    #[test]
+    fn template_filters() {
+        let my_context = MyContext {
+            name: "John \"Jo\" Doe".to_string(),
+            a: 1,
+            age: String::new()
+        };
+        let render = |template: &str| PromptTemplate::new(template).unwrap()
+            .prompt_str(&my_context)
+            .map(|rendered| rendered.unwrap().into_owned());
+        assert_eq!(render("{name|upper}").unwrap(), "JOHN \"JO\" DOE");
+        assert_eq!(render("{name|truncate:4}").unwrap(), "John");
+        assert_eq!(render("{name | truncate: 4, \"...\" | lower}").unwrap(), "john...");
+        assert_eq!(render("{name|json_escape}").unwrap(), "John \\\"Jo\\\" Doe");
+        assert_eq!(render("{age|default:\"n/a\"} {missing|default:\"{none}\"}").unwrap(), "n/a {none}");
+        assert!(matches!(render("{missing|upper}"), Err(PromptError::MissingContextVar(name)) if name == "missing"));
+
+        let items = HashMap::from([("items".to_string(), "a\nb\nc".to_string())]);
+        let template = PromptTemplate::new("{items|join:\", \"}").unwrap();
+        assert_eq!(template.prompt_str_with(&my_context, &items).unwrap().unwrap(), "a, b, c");
+
+        let filters = FilterRegistry::new()
+            .filter("shout", |value: &str, args: &[String]| Ok(format!("{value}{}", "!".repeat(args.len().max(1)))));
+        let template = PromptTemplate::with_filters("{name|truncate:4|shout:x,y}", &filters).unwrap();
+        assert_eq!(template.prompt_str(&my_context).unwrap().unwrap(), "John!!");
+        assert!(filters.names().contains(&"upper"));
+
+        assert!(matches!(parse_error("{name|shout}"), Some(TemplateErrorKind::UnknownFilter(name)) if name == "shout"));
+        assert!(matches!(parse_error("{name|truncate:many}"), Some(TemplateErrorKind::InvalidFilterArgs { filter, .. }) if filter == "truncate"));
+        assert!(matches!(parse_error("{name|upper:1}"), Some(TemplateErrorKind::InvalidFilterArgs { .. })));
+        assert!(matches!(parse_error("{name|}"), Some(TemplateErrorKind::EmptyFilter(_))));
+        assert!(matches!(parse_error("{name|default:\"n/a}"), Some(TemplateErrorKind::UnterminatedString)));
+    }
+    #[test]
+    fn template_blocks() {
+        let my_context = MyContext {
+            name: "John".to_string(),
+            a: 0,
+            age: "18".to_string()
+        };
+        let template = PromptTemplate::new(
+            "Hi {name}.\n  {% if age %}\nYou are {age}.\n\t{% endif %}\n{% if not a %}No a.{% else %}a is {a}.{% endif %}"
+        ).unwrap();
+        assert_eq!(template.prompt_str(&my_context).unwrap().unwrap(), "Hi John.\nYou are 18.\nNo a.");
+
+        let template = PromptTemplate::new(
+            "{% for doc in docs %}\n- {doc|upper}{% if name %} for {name}{% endif %}\n{% endfor %}\n{% if history|trim %}{history}{% else %}No history.{% endif %}"
+        ).unwrap();
+        let vars = HashMap::from([
+            ("docs".to_string(), "first\nsecond".to_string()),
+            ("history".to_string(), "  ".to_string()),
+        ]);
+        assert_eq!(template.prompt_str_with(&my_context, &vars).unwrap().unwrap(), "- FIRST for John\n- SECOND for John\nNo history.");
+        assert!(matches!(template.prompt_str(&my_context), Err(PromptError::MissingContextVar(name)) if name == "docs"));
+
+        let template = PromptTemplate::new("{% for name in docs %}{name} {% endfor %}{name}").unwrap();
+        let vars = HashMap::from([("docs".to_string(), "a\nb".to_string())]);
+        assert_eq!(template.prompt_str_with(&my_context, &vars).unwrap().unwrap(), "a b John");
+
+        assert!(matches!(parse_error("{% if name %}open"), Some(TemplateErrorKind::UnclosedBlock(block)) if block == "if"));
+        assert!(matches!(parse_error("{% for x in xs %}{% endif %}"), Some(TemplateErrorKind::UnexpectedTag(tag)) if tag == "endif"));
+        assert!(matches!(parse_error("{% else %}"), Some(TemplateErrorKind::UnexpectedTag(_))));
+        assert!(matches!(parse_error("{% if a %}{% else %}{% else %}{% endif %}"), Some(TemplateErrorKind::UnexpectedTag(_))));
+        assert!(matches!(parse_error("{% for x of xs %}{% endfor %}"), Some(TemplateErrorKind::InvalidTag(_))));
+        assert!(matches!(parse_error("{% while a %}"), Some(TemplateErrorKind::UnknownTag(tag)) if tag == "while"));
+        assert!(matches!(parse_error("{% if a "), Some(TemplateErrorKind::UnterminatedTag)));
+    }
+    #[test]
+    fn template_values() {
+        let mut context = DefaultContext::new();
+        context.insert("user".to_string(), TemplateValue::map([
+            ("name", TemplateValue::from("Ada")),
+            ("admin", TemplateValue::from(false)),
+            ("address", TemplateValue::map([("city", "London")])),
+        ]));
+        context.insert("docs".to_string(), TemplateValue::from(vec![
+            TemplateValue::map([("title", TemplateValue::from("Notes")), ("pages", TemplateValue::from(12))]),
+            TemplateValue::map([("title", TemplateValue::from("Letters")), ("pages", TemplateValue::from(2.5))]),
+        ]));
+        context.insert("tags".to_string(), TemplateValue::from(vec!["math", "poetry"]));
+        context.insert("plain".to_string(), "text".to_string());
+        context.insert("notes".to_string(), TemplateValue::from(vec!["two\nlines", "one"]));
+
+        let render = |template: &str| PromptTemplate::new(template).unwrap()
+            .prompt_str(&context)
+            .map(|rendered| rendered.unwrap().into_owned());
+        assert_eq!(render("{user.name} from {user.address.city}, {plain}.").unwrap(), "Ada from London, text.");
+        assert_eq!(render("{docs[0].title} has {docs[0].pages} pages, {docs.1.title} {docs[1].pages}.").unwrap(), "Notes has 12 pages, Letters 2.5.");
+        assert_eq!(render("{tags|join:\", \"} / {tags[1]|upper}").unwrap(), "math, poetry / POETRY");
+        assert_eq!(render("{notes|join:\" | \"}").unwrap(), "two\nlines | one");
+        assert_eq!(render("{% for doc in docs %}{doc.title}:{doc.pages} {% endfor %}").unwrap(), "Notes:12 Letters:2.5 ");
+        assert_eq!(render("{% for entry in user.address %}{entry.key}={entry.value}{% endfor %}").unwrap(), "city=London");
+        assert_eq!(render("{% if user.admin %}admin{% else %}user{% endif %}{% if docs %}, docs{% endif %}{% if docs[5] %}, five{% endif %}").unwrap(), "user, docs");
+        assert_eq!(render("{user.address}").unwrap(), "city: London");
+        assert_eq!(context.template_var("tags"), Some("math\npoetry".to_string()));
+        assert_eq!(context.template_var("plain"), Some("text".to_string()));
+        assert!(matches!(render("{docs[2].title}"), Err(PromptError::MissingContextVar(name)) if name == "docs[2].title"));
+        assert!(matches!(render("{% for page in docs[0].pages %}{% endfor %}"), Err(PromptError::NotIterable(_))));
+
+        assert!(matches!(parse_error("{docs[x]}"), Some(TemplateErrorKind::InvalidPath(_))));
+        assert!(matches!(parse_error("{user..name}"), Some(TemplateErrorKind::InvalidIdentifier(_))));
+        assert!(matches!(parse_error("{docs[0}"), Some(TemplateErrorKind::InvalidPath(_))));
+    }
+    #[test]
+    fn template_variables_validation() {
+        let my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let template = PromptTemplate::new(
+            "{name} {topic}{% if flag %} {extra}{% endif %} {tone|default:\"plain\"}{% for doc in docs %}{doc.title} {name}{% endfor %}{topic}"
+        ).unwrap();
+        let prompt = PromptVariant::<MyContext>::from(template);
+        assert_eq!(prompt.variables().into_iter().collect::<Vec<_>>(), vec!["docs", "extra", "flag", "name", "tone", "topic"]);
+        assert_eq!(prompt.required_variables().into_iter().collect::<Vec<_>>(), vec!["docs", "extra", "name", "topic"]);
+        assert_eq!(prompt.missing_variables(&my_context), vec!["topic", "docs"]);
+        assert!(matches!(prompt.validate(&my_context), Err(PromptError::MissingContextVars(names)) if names == ["topic", "docs"]));
+        assert!(matches!(prompt.validate_type(), Err(PromptError::MissingContextVars(names)) if names == ["docs", "extra", "topic"]));
+
+        let mut chain = PromptChain::<MyContext>::new();
+        chain.push(PromptTemplate::new("I'm {name}, {age}.").unwrap());
+        chain.push(IfPromptBuilder::new()
+            .then(PromptTemplate::new("{a} is big, {size}.").unwrap())
+            .otherwise(PromptTemplate::new("{a} is small, {size}.").unwrap())
+            .condition(|my_context: &MyContext| my_context.a > 3)
+            .build().unwrap());
+        chain.push(RolePart::system(PromptTemplate::new("Be {mood}.").unwrap()));
+        assert_eq!(chain.variables().into_iter().collect::<Vec<_>>(), vec!["a", "age", "mood", "name", "size"]);
+        assert_eq!(chain.missing_variables(&my_context), vec!["size", "mood"]);
+        assert!(matches!(chain.validate_type(), Err(PromptError::MissingContextVars(names)) if names == ["mood", "size"]));
+
+        let mut chain = PromptChain::<MyContext>::new();
+        chain.push("Hello");
+        chain.push(PromptTemplate::new("{name|upper}{% if missing %}{missing}{% endif %}").unwrap());
+        assert!(chain.validate(&my_context).is_ok());
+        assert!(chain.validate_type().is_err());
+    }
+    #[test]
+    fn template_diagnostics() {
+        let error = PromptTemplate::new("Summary:\n\tHello {name|shout}!").err().unwrap().with_origin("greeting.txt");
+        assert_eq!(error.kind(), &TemplateErrorKind::UnknownFilter("shout".to_string()));
+        assert_eq!((error.line(), error.column(), error.source_line()), (2, 14, "\tHello {name|shout}!"));
+        assert_eq!(error.to_string(), "Invalid prompt template at line 2, column 14: unknown filter `shout`.");
+        assert_eq!(error.diagnostic(), [
+            "error: unknown filter `shout`",
+            " --> greeting.txt:2:14",
+            "  |",
+            "2 | \tHello {name|shout}!",
+            "  | \t            ^^^^^ not a registered filter",
+            "  |",
+            "  = help: available filters: default, join, json_escape, lower, trim, truncate, upper",
+        ].join("\n"));
+
+        let position = |template: &str| PromptTemplate::new(template).err().map(|error| (error.kind().clone(), error.line(), error.column()));
+        assert_eq!(position("Hi {name.\nBye"), Some((TemplateErrorKind::UnclosedBrace, 1, 4)));
+        assert_eq!(position("Hi {name}}"), Some((TemplateErrorKind::StrayBrace, 1, 10)));
+        assert_eq!(position("{a {b}}"), Some((TemplateErrorKind::NestedBrace, 1, 4)));
+        assert_eq!(position("One\nTwo {first name}"), Some((TemplateErrorKind::InvalidIdentifier("first name".to_string()), 2, 6)));
+        assert_eq!(position("{docs[0].ti-tle}"), Some((TemplateErrorKind::InvalidIdentifier("ti-tle".to_string()), 1, 10)));
+        assert_eq!(position("{{literal}} {x|default:\"{\"} {x|truncate:\"a\"}"), Some((TemplateErrorKind::InvalidFilterArgs {
+            filter: "truncate".to_string(),
+            message: "length must be a number, got `a`".to_string(),
+        }, 1, 41)));
+
+        let error = PromptTemplate::new("Intro\n  {% if a %}\nbody").err().unwrap();
+        assert_eq!((error.line(), error.column(), error.help()), (2, 3, Some("close it with `{% endif %}`")));
+        assert!(error.diagnostic().starts_with("error: unclosed `if` block\n --> template:2:3\n  |\n2 |   {% if a %}\n  |   ^^^^^^^^^^ this block is never closed"));
+    }
+    #[cfg(feature = "executable")]
+    fn text(response: ChatResponse, _: &mut MyContext) -> Result<Option<String>, ProcessorError> {
+        Ok(response.first_content().map(str::to_string))
+    }
+    #[cfg(feature = "executable")]
     #[tokio::test]
     async fn plain_execute_chain() {
         let mut my_context = MyContext {
@@ -233,15 +470,12 @@ mod tests {
             a: 1,
             age: "18".to_string()
         };
-        dotenvy::dotenv().ok();
-        let api_key = dotenvy::var("TEST_API_KEY")
-            .expect("TEST_API_KEY must be set");
-        let client = Client::with_config(
-            OpenAIConfig::new()
-                .with_api_base("https://api.siliconflow.cn/v1")
-                .with_api_key(api_key)
-
-        );
+        let backend = ScriptedBackend::new()
+            .reply("1")
+            .reply("2")
+            .reply("3")
+            .reply("4")
+            .reply("5");
 
         let mut chain = PromptChain::<MyContext>::new();
         chain.push("Hello");
@@ -263,22 +497,32 @@ mod tests {
         chain.push("Can I be reached?");
         let mut flow = chain.flow();
         while let Some(prompt) = flow.next_with(&my_context) {
-            println!("{:?}", prompt.prompt_str(&my_context));
-            println!("a: {}", my_context.a);
-            prompt
-                .to_executable(|_, _| {Ok(None::<String>)})
+            let answer = prompt
+                .to_executable(text)
                 .models(
                     vec![
                         "Qwen/Qwen3-8B"
                     ]
                 )
-                .execute(&mut my_context, &client, None)
+                .execute(&mut my_context, &backend, None)
                 .await
                 .unwrap();
+            assert_eq!(answer, Some(my_context.a.to_string()));
             my_context.a += 1;
         }
+        let sent = backend.requests()
+            .into_iter()
+            .map(|request| request.messages[0].content.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(sent, vec![
+            "Hello",
+            "I'm John.\n18 years old.\ncrazy 2.",
+            "Else block here!",
+            "Loop block here!",
+            "Can I be reached?"
+        ]);
     }
-    #[cfg(feature = "async_oai")]
+    #[cfg(feature = "executable")]
     #[cfg(feature = "send")]
     #[tokio::test]
     async fn send_execute_chain() {
@@ -287,15 +531,12 @@ mod tests {
             a: 1,
             age: "18".to_string()
         };
-        dotenvy::dotenv().ok();
-        let api_key = dotenvy::var("TEST_API_KEY")
-            .expect("TEST_API_KEY must be set");
-        let client = Client::with_config(
-            OpenAIConfig::new()
-                .with_api_base("https://api.siliconflow.cn/v1")
-                .with_api_key(api_key)
-
-        );
+        let backend = ScriptedBackend::new()
+            .reply("1")
+            .reply("2")
+            .reply("3")
+            .reply("4")
+            .reply("5");
 
         let mut chain = SendPromptChain::<MyContext>::new();
         chain.push("Hello");
@@ -317,38 +558,451 @@ mod tests {
         chain.push("Can I be reached?");
         let mut flow = chain.flow();
         while let Some(prompt) = flow.next_with(&my_context) {
-            println!("{:?}", prompt.prompt_str(&my_context));
-            println!("a: {}", my_context.a);
-            prompt
-                .to_executable(|_, _| {Ok(None::<String>)})
+            let answer = prompt
+                .to_executable(text)
                 .models(
                     vec![
                         "Qwen/Qwen3-8B"
                     ]
                 )
-                .execute(&mut my_context, &client, None)
+                .execute(&mut my_context, &backend, None)
                 .await
                 .unwrap();
+            assert_eq!(answer, Some(my_context.a.to_string()));
             my_context.a += 1;
         }
+        assert_eq!(backend.requests().len(), 5);
+        assert_eq!(backend.remaining(), 0);
     }
-    #[cfg(feature = "retry")]
+    #[cfg(feature = "executable")]
     #[tokio::test]
-    async fn retry_execute_chain() {
+    async fn executable_flow_branches() {
         let mut my_context = MyContext {
             name: "John".to_string(),
             a: 1,
             age: "18".to_string()
         };
-        dotenvy::dotenv().ok();
-        let api_key = dotenvy::var("TEST_API_KEY")
-            .expect("TEST_API_KEY must be set");
-        let client = Client::with_config(
-            OpenAIConfig::new()
-                .with_api_base("https://api.siliconflow.cn/v1")
-                .with_api_key(api_key)
+        let backend = ScriptedBackend::new()
+            .reply("a")
+            .reply("b")
+            .reply("c")
+            .reply("d")
+            .reply("e");
+        let hello = PromptVariant::from("Hello");
+        let then = PromptVariant::from("Then");
+        let otherwise = PromptVariant::from("Otherwise");
+        let skipped = PromptVariant::from("Skipped");
+        let looped = PromptVariant::from("Looped");
+        let last = PromptVariant::from("Last");
 
+        let mut chain = ExecutablePromptChain::<MyContext, String>::new();
+        chain.push(hello.to_executable(text).models(vec!["m"]));
+        chain.push(
+            ExecutableIfPromptBuilder::new()
+                .then(then.to_executable(text).models(vec!["m"]))
+                .otherwise(otherwise.to_executable(text).models(vec!["m"]))
+                .condition(|my_context: &MyContext| my_context.a > 1)
+                .build().unwrap()
+        );
+        chain.push(
+            ExecutableIfPromptBuilder::new()
+                .then(skipped.to_executable(text).models(vec!["m"]))
+                .condition(|my_context: &MyContext| my_context.a > 10)
+                .build().unwrap()
         );
+        chain.push(
+            ExecutableLoopPromptBuilder::new()
+                .prompt(looped.to_executable(text).models(vec!["m"]))
+                .condition(|my_context: &MyContext| my_context.a < 5)
+                .build().unwrap()
+        );
+        chain.push(last.to_executable(text).models(vec!["m"]));
+        let mut flow = chain.flow();
+        let mut answers = Vec::new();
+        while let Some(prompt) = flow.next_with(&my_context) {
+            answers.push(prompt.execute(&mut my_context, &backend, None).await.unwrap().unwrap());
+            my_context.a += 1;
+        }
+        assert_eq!(answers, vec!["a", "b", "c", "d", "e"]);
+        let sent = backend.requests()
+            .into_iter()
+            .map(|request| request.messages[0].content.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(sent, vec!["Hello", "Then", "Looped", "Looped", "Last"]);
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn flow_records_conversation() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .reply("Hi John")
+            .reply("Noted")
+            .reply("You are 18");
+        let greet = PromptVariant::from(PromptTemplate::new("I'm {name}.").unwrap());
+        let aside = PromptVariant::from("Ignore this.");
+        let ask = PromptVariant::from("How old am I?");
+
+        let mut chain = ExecutablePromptChain::<MyContext, String>::new();
+        chain.push(greet.to_executable(text).models(vec!["m"]));
+        chain.push(aside.to_executable(text).models(vec!["m"]).history(HistoryMode::Exclude));
+        chain.push(ask.to_executable(text).models(vec!["m"]));
+        let mut flow = chain.flow()
+            .with_conversation(Conversation::new().with_system("Be brief."));
+        while let Some(result) = flow.execute_next(&mut my_context, &backend).await {
+            result.unwrap();
+        }
+        let requests = backend.requests();
+        assert_eq!(requests[1].messages, vec![ChatMessage::user("Ignore this.")]);
+        assert_eq!(requests[2].messages, vec![
+            ChatMessage::system("Be brief."),
+            ChatMessage::user("I'm John."),
+            ChatMessage::assistant("Hi John"),
+            ChatMessage::user("How old am I?"),
+        ]);
+        assert_eq!(flow.conversation().len(), 5);
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn composed_role_prompt() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .reply(" Hello John")
+            .reply("Bye");
+        let persona = PromptVariant::from(ComposedPrompt::new()
+            .system("You are a pirate.")
+            .user(PromptTemplate::new("I'm {name}.").unwrap())
+            .assistant("Arr,"));
+        let farewell = PromptVariant::from("Bye!").with_role(Role::Developer);
+        assert_eq!(persona.prompt_str(&my_context).unwrap().unwrap(), "You are a pirate.\nI'm John.\nArr,");
+
+        let mut chain = ExecutablePromptChain::<MyContext, String>::new();
+        chain.push(persona.to_executable(text).models(vec!["m"]));
+        chain.push(farewell.to_executable(text).models(vec!["m"]));
+        let mut flow = chain.flow();
+        while let Some(result) = flow.execute_next(&mut my_context, &backend).await {
+            result.unwrap();
+        }
+        let requests = backend.requests();
+        assert_eq!(requests[0].messages, vec![
+            ChatMessage::system("You are a pirate."),
+            ChatMessage::user("I'm John."),
+            ChatMessage::assistant("Arr,"),
+        ]);
+        assert_eq!(requests[1].messages[0], ChatMessage::user("I'm John."));
+        assert_eq!(requests[1].messages.last(), Some(&ChatMessage::developer("Bye!")));
+        assert_eq!(flow.conversation().messages(), &[
+            ChatMessage::user("I'm John."),
+            ChatMessage::assistant("Arr, Hello John"),
+            ChatMessage::user("Bye!"),
+            ChatMessage::assistant("Bye"),
+        ]);
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn step_params_override_chain_defaults() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .reply("{}")
+            .reply("Once upon a time");
+        let extract = PromptVariant::from("Extract the fields.");
+        let story = PromptVariant::from("Tell a story.");
+
+        let mut chain = ExecutablePromptChain::<MyContext, String>::new()
+            .with_defaults(GenerationParams::new().temperature(0.7).max_tokens(256));
+        chain.push(extract.to_executable(text).models(vec!["m"]).temperature(0.0).seed(42).stop(["\n\n"]));
+        chain.push(story.to_executable(text).models(vec!["m"]).top_p(0.9));
+        let mut flow = chain.flow();
+        while let Some(result) = flow.execute_next(&mut my_context, &backend).await {
+            result.unwrap();
+        }
+        let requests = backend.requests();
+        assert_eq!(requests[0].params, GenerationParams::new()
+            .temperature(0.0)
+            .max_tokens(256)
+            .stop(["\n\n"])
+            .seed(42));
+        assert_eq!(requests[1].params, GenerationParams::new()
+            .temperature(0.7)
+            .top_p(0.9)
+            .max_tokens(256));
+
+        let single = extract.to_executable(text).models(vec!["m"]).max_tokens(16);
+        let backend = ScriptedBackend::new().reply("{}");
+        single.execute(&mut my_context, &backend, None).await.unwrap();
+        assert_eq!(backend.requests()[0].params, GenerationParams::new().max_tokens(16));
+    }
+    #[cfg(feature = "schema")]
+    #[derive(Debug, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+    struct Person {
+        name: String,
+        age: u32,
+    }
+    #[cfg(feature = "schema")]
+    #[tokio::test]
+    async fn structured_output_from_schema() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .reply(r#"{"name": "John", "age": 18}"#)
+            .reply("not json");
+        let prompt = PromptVariant::from(PromptTemplate::new("{name} is {age}.").unwrap());
+        let executable = prompt.to_structured_executable::<Person>().models(vec!["m"]);
+
+        let person = executable.execute(&mut my_context, &backend, None).await.unwrap();
+        assert_eq!(person, Some(Person { name: "John".to_string(), age: 18 }));
+        match &backend.requests()[0].response_format {
+            Some(ResponseFormat::JsonSchema { name, schema, .. }) => {
+                assert_eq!(name, "Person");
+                assert!(schema["properties"]["age"].is_object());
+            },
+            other => panic!("unexpected response format {other:?}"),
+        }
+        assert!(matches!(
+            executable.execute(&mut my_context, &backend, None).await.unwrap_err(),
+            PromptExecutableError::Processor(ProcessorError::Deserialize(_))
+        ));
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn builtin_processors() {
+        let mut context = DefaultContext::new();
+        let backend = ScriptedBackend::new()
+            .reply("Sure:\n```json\n[1, 2]\n```")
+            .reply("- apples\n2) pears\n\n* plums")
+            .reply("\"Positive\".")
+            .reply("Maybe")
+            .reply("Total: 42 EUR");
+        let prompt = PromptVariant::from("Go.");
+
+        let numbers = prompt.to_executable(processor::fenced_json::<_, Vec<u32>>).models(vec!["m"]);
+        assert_eq!(numbers.execute(&mut context, &backend, None).await.unwrap(), Some(vec![1, 2]));
+
+        let fruits = prompt.to_executable(processor::store_into("fruits", processor::lines)).models(vec!["m"]);
+        let expected = vec!["apples".to_string(), "pears".to_string(), "plums".to_string()];
+        assert_eq!(fruits.execute(&mut context, &backend, None).await.unwrap(), Some(expected.clone()));
+        assert_eq!(context.get::<Vec<String>>("fruits"), Some(&expected));
+
+        let sentiment = prompt.to_executable(processor::label([("positive", true), ("negative", false)])).models(vec!["m"]);
+        assert_eq!(sentiment.execute(&mut context, &backend, None).await.unwrap(), Some(true));
+        assert!(sentiment.execute(&mut context, &backend, None).await.is_err());
+
+        let total = prompt.to_executable(processor::text).models(vec!["m"]);
+        assert_eq!(total.execute(&mut context, &backend, None).await.unwrap(), Some("Total: 42 EUR".to_string()));
+    }
+    #[cfg(feature = "regex")]
+    #[tokio::test]
+    async fn regex_capture_processor() {
+        let backend = ScriptedBackend::new()
+            .reply("Total: 42 EUR")
+            .reply("No total");
+        let prompt = PromptVariant::from("Go.");
+        let total = prompt.to_executable(processor::capture(regex::Regex::new(r"Total: (\d+)").unwrap(), 1)).models(vec!["m"]);
+        assert_eq!(total.execute(&mut (), &backend, None).await.unwrap(), Some("42".to_string()));
+        assert!(total.execute(&mut (), &backend, None).await.is_err());
+    }
+    #[cfg(feature = "executable")]
+    #[derive(serde::Deserialize)]
+    struct Add {
+        n: i32,
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn tool_calls_loop() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .call_tool("add", serde_json::json!({"n": 2}))
+            .call_tools([("missing", serde_json::json!({})), ("add", serde_json::json!({"n": "x"}))])
+            .reply("a is 3");
+        let prompt = PromptVariant::from("Add 2 to a.");
+        let add = Tool::new("add", serde_json::json!({"type": "object"}), |args: Add, context: &mut MyContext| {
+            context.a += args.n;
+            Ok::<_, String>(context.a)
+        }).description("Adds n to a");
+        let executable = prompt.to_executable(text).models(vec!["m"]).tool(add);
+
+        let mut conversation = Conversation::new();
+        let result = executable.execute_in(&mut my_context, &backend, None, &mut conversation).await;
+        assert_eq!(result.unwrap().as_deref(), Some("a is 3"));
+        assert_eq!(my_context.a, 3);
+
+        let requests = backend.requests();
+        assert_eq!(requests[0].tools[0].name, "add");
+        assert_eq!(requests[0].tools[0].description.as_deref(), Some("Adds n to a"));
+        let last = &requests[2].messages;
+        assert_eq!(last.len(), 6);
+        assert_eq!(last[1].tool_calls[0].name, "add");
+        assert_eq!(last[2], ChatMessage::tool("call-0-0", "3"));
+        assert!(last[4].content.contains("unknown tool `missing`"));
+        assert!(last[5].content.contains("invalid arguments"));
+        assert_eq!(conversation.len(), 7);
+
+        let backend = ScriptedBackend::new()
+            .call_tool("add", serde_json::json!({"n": 1}))
+            .call_tool("add", serde_json::json!({"n": 1}));
+        let executable = executable.max_tool_iterations(1);
+        assert!(matches!(
+            executable.execute(&mut my_context, &backend, None).await.unwrap_err(),
+            PromptExecutableError::ToolIterationLimit(1)
+        ));
+    }
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn stream_execution() {
+        use futures::StreamExt;
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new().reply("Hello John Smith");
+        let prompt = PromptVariant::from(PromptTemplate::new("Greet {name}.").unwrap());
+        let executable = prompt.to_executable(text).models(vec!["m"]);
+        let events = executable.execute_stream(&mut my_context, &backend, None)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![
+            StreamEvent::Delta("Hello ".to_string()),
+            StreamEvent::Delta("John ".to_string()),
+            StreamEvent::Delta("Smith".to_string()),
+            StreamEvent::Done(Some("Hello John Smith".to_string())),
+        ]);
+    }
+    #[cfg(all(feature = "stream", feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn stream_restarts_on_stream_error() {
+        use futures::StreamExt;
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .interrupted("\"o")
+            .reply("\"ok\"");
+        let prompt = SendPromptVariant::from("Hello");
+        let events = prompt.to_retry_executable(processor::json)
+            .models(vec!["m"])
+            .execute_stream_with_retry(&mut my_context, &backend, None, 2)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![
+            StreamEvent::Delta("\"o".to_string()),
+            StreamEvent::Reset,
+            StreamEvent::Delta("\"ok\"".to_string()),
+            StreamEvent::Done(Some("ok".to_string())),
+        ]);
+    }
+    #[cfg(feature = "executable")]
+    #[test]
+    fn conversation_window() {
+        let mut conversation = Conversation::new()
+            .with_system("Be brief.")
+            .with_window(HistoryWindow::LastTurns(1));
+        for turn in ["one", "two", "three"] {
+            conversation.push(ChatMessage::user(turn));
+            conversation.push(ChatMessage::assistant(turn.to_uppercase()));
+        }
+        assert_eq!(conversation.history(), vec![
+            ChatMessage::system("Be brief."),
+            ChatMessage::user("three"),
+            ChatMessage::assistant("THREE"),
+        ]);
+        conversation.set_window(HistoryWindow::LastMessages(3));
+        assert_eq!(conversation.history(), vec![
+            ChatMessage::system("Be brief."),
+            ChatMessage::assistant("TWO"),
+            ChatMessage::user("three"),
+            ChatMessage::assistant("THREE"),
+        ]);
+        conversation.set_window(HistoryWindow::LastTurns(0));
+        assert_eq!(conversation.history(), vec![ChatMessage::system("Be brief.")]);
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test(start_paused = true)]
+    async fn scripted_latency() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .reply("late")
+            .with_latency(Duration::from_secs(5));
+        let prompt = PromptVariant::from("Hello");
+        let start = tokio::time::Instant::now();
+        let answer = prompt
+            .to_executable(text)
+            .models(vec!["m"])
+            .execute(&mut my_context, &backend, None)
+            .await
+            .unwrap();
+        assert_eq!(answer.as_deref(), Some("late"));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    async fn scripted_retry(
+        backend: &ScriptedBackend,
+        template: &str,
+        models: Vec<&str>,
+        select_model: Option<usize>,
+        policy: impl Into<RetryPolicy>
+    ) -> Result<Option<String>, PromptExecutableError> {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let prompt = SendPromptVariant::from(PromptTemplate::new(template).unwrap());
+        prompt
+            .to_retry_executable(processor::json)
+            .models(models)
+            .execute_with_retry(&mut my_context, backend, select_model)
+            .await
+            .retry(policy)
+            .await
+            .into_result()
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_execute_chain() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .reply("\"1\"")
+            .api_error("503")
+            .reply("\"2\"")
+            .reply("\"3\"")
+            .malformed_response()
+            .reply("\"4\"")
+            .reply("\"5\"");
 
         let mut chain = SendPromptChain::<MyContext>::new();
         chain.push("Hello");
@@ -368,24 +1022,765 @@ mod tests {
             .build().unwrap();
         chain.push(loop_prompt);
         chain.push("Can I be reached?");
+        tokio::time::pause();
         let mut flow = chain.flow();
         while let Some(prompt) = flow.next_with(&my_context) {
-            println!("{:?}", prompt.prompt_str(&my_context));
-            println!("a: {}", my_context.a);
-            prompt
-                .to_retry_executable(|_, _| {Ok(None::<String>)})
+            let answer = prompt
+                .to_retry_executable(processor::json)
                 .models(
                     vec![
                         "Qwen/Qwen3-8B"
                     ]
                 )
-                .execute_with_retry(&mut my_context, &client, None)
+                .execute_with_retry(&mut my_context, &backend, None)
                 .await
                 .retry(3)
                 .await
                 .unwrap();
-
+            assert_eq!(answer, Some(my_context.a.to_string()));
             my_context.a += 1;
         }
+        assert_eq!(backend.remaining(), 0);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_model_not_set() {
+        let backend = ScriptedBackend::new().reply("\"unused\"");
+        let result = scripted_retry(&backend, "Hello", vec![], None, 3).await;
+        assert!(matches!(result, Err(PromptExecutableError::ModelNotSet)));
+        assert!(backend.requests().is_empty());
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_invalid_model_selection() {
+        let backend = ScriptedBackend::new().reply("\"ok\"");
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], Some(3), 3).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(backend.requested_models(), vec!["m1"]);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_fail_building_prompt() {
+        let backend = ScriptedBackend::new().reply("\"unused\"");
+        let result = scripted_retry(&backend, "Hello {nobody}", vec!["m1"], None, 3).await;
+        assert!(matches!(result, Err(PromptExecutableError::FailBuildingPrompt(_))));
+        assert!(backend.requests().is_empty());
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_transient_backend_errors() {
+        let backend = ScriptedBackend::new()
+            .fail(BackendError::Transport("connection reset".into()))
+            .fail(BackendError::Stream("stream closed".to_string()))
+            .malformed_response()
+            .reply("\"ok\"");
+        let result = scripted_retry(&backend, "Hello", vec!["m1", "m2"], None, 3).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(backend.requested_models(), vec!["m1", "m1", "m1", "m1"]);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_rate_limit_rotates_models() {
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .api_error("429")
+            .reply("\"ok\"");
+        let start = tokio::time::Instant::now();
+        let result = scripted_retry(&backend, "Hello", vec!["m1", "m2"], Some(1), 3).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(backend.requested_models(), vec!["m2", "m1", "m2"]);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_rate_limit_single_model_waits() {
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .reply("\"ok\"");
+        let start = tokio::time::Instant::now();
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, RetryPolicy::new().jitter(0.0)).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_server_errors_wait() {
+        let backend = ScriptedBackend::new()
+            .api_error("500")
+            .api_error("503")
+            .reply("\"ok\"");
+        let start = tokio::time::Instant::now();
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, RetryPolicy::new().jitter(0.0)).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_policy_backoff() {
+        let policy = RetryPolicy::new()
+            .initial_delay(Duration::from_millis(100))
+            .multiplier(3.0)
+            .max_delay(Duration::from_secs(1))
+            .jitter(0.0);
+        assert_eq!(policy.backoff_delay(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff_delay(2), Duration::from_millis(900));
+        assert_eq!(policy.backoff_delay(3), Duration::from_secs(1));
+        assert_eq!(policy.backoff_delay(usize::MAX), Duration::from_secs(1));
+        let jittered = policy.jitter(0.5);
+        for _ in 0..32 {
+            let delay = jittered.backoff_delay(2);
+            assert!(delay > Duration::from_millis(450) && delay <= Duration::from_millis(900));
+        }
+
+        let backend = ScriptedBackend::new()
+            .api_error("500")
+            .api_error("500")
+            .api_error("500")
+            .reply("\"ok\"");
+        let start = tokio::time::Instant::now();
+        let policy = RetryPolicy::new()
+            .max_retries(5)
+            .jitter(0.0)
+            .deadline(Duration::from_secs(5));
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, policy).await;
+        assert!(matches!(result, Err(PromptExecutableError::RetryFail(_))));
+        assert_eq!(backend.remaining(), 1);
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn token_budget_truncation() {
+        let tokenizer = HeuristicTokenizer::new();
+        assert_eq!(tokenizer.count("abcdefghi"), 3);
+        assert_eq!(tokenizer.truncate("abcdefghij", 2), "abcdefgh");
+        assert_eq!(tokenizer.truncate("abc", 2), "abc");
+
+        let mut context = MyContext {
+            name: "x".repeat(400),
+            a: 1,
+            age: String::new()
+        };
+        let prompt = PromptVariant::from(PromptTemplate::new("Summarize: {name}").unwrap());
+        let small = ModelSpec::new("small").context_window(60);
+        let executable = prompt.to_executable(text).models(small.clone()).max_tokens(10);
+        assert!(matches!(
+            executable.check_tokens(&context, None),
+            Err(PromptExecutableError::ContextWindowExceeded { tokens: 107, limit: 50, .. })
+        ));
+        let backend = ScriptedBackend::new().reply("ok");
+        let executable = executable.token_budget(TokenBudget::new().truncate_var("name"));
+        executable.execute(&mut context, &backend, None).await.unwrap();
+        assert_eq!(backend.requests()[0].messages[0].content, format!("Summarize: {}", "x".repeat(172)));
+
+        let backend = ScriptedBackend::new().reply("ok");
+        let executable = prompt.to_executable(text)
+            .models(ModelList::new().model(small).model(ModelSpec::new("large").context_window(1_000)).fallback(Fallback::Downgrade))
+            .token_budget(TokenBudget::new());
+        executable.execute(&mut context, &backend, None).await.unwrap();
+        assert_eq!(backend.requested_models(), ["large"]);
+
+        let mut conversation = Conversation::new().with_system("sys");
+        for turn in ["first", "second"] {
+            conversation.push(ChatMessage::user(turn.repeat(40)));
+            conversation.push(ChatMessage::assistant(turn.repeat(40)));
+        }
+        let greeting = PromptVariant::from("Hi");
+        let executable = greeting.to_executable(text)
+            .models(ModelSpec::new("m").context_window(150))
+            .token_budget(TokenBudget::new().truncate_history());
+        let backend = ScriptedBackend::new().reply("ok");
+        executable.execute_in(&mut context, &backend, None, &mut conversation).await.unwrap();
+        let sent = backend.requests()[0].messages.iter()
+            .map(|message| message.content.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(sent, ["sys".to_string(), "second".repeat(40), "second".repeat(40), "Hi".to_string()]);
+        assert_eq!(conversation.len(), 7);
+
+        let mut docs = DefaultContext::new();
+        docs.insert("docs".to_string(), TemplateValue::from(vec![TemplateValue::map([("title", "x".repeat(400))])]));
+        let summary = PromptVariant::from(PromptTemplate::new("Summarize: {docs[0].title}").unwrap());
+        let executable = summary.to_executable(processor::text)
+            .models(ModelSpec::new("small").context_window(60))
+            .token_budget(TokenBudget::new().truncate_var("docs"));
+        let backend = ScriptedBackend::new().reply("unused");
+        let result = executable.execute(&mut docs, &backend, None).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::ContextWindowExceeded { .. }));
+        assert!(backend.requests().is_empty());
+
+        #[cfg(all(feature = "retry", feature = "send"))]
+        {
+            let prompt = SendPromptVariant::from(PromptTemplate::new("Summarize: {name}").unwrap());
+            let executable = prompt.to_retry_executable(text)
+                .models(ModelSpec::new("small").context_window(60))
+                .max_tokens(10)
+                .token_budget(TokenBudget::new().truncate_var("name"));
+            assert!(matches!(
+                executable.check_tokens(&context, None),
+                Err(PromptExecutableError::ContextWindowExceeded { tokens: 107, limit: 50, .. })
+            ));
+            let backend = ScriptedBackend::new().reply("ok");
+            executable.execute_with_retry(&mut context, &backend, None).await.retry(0).await.unwrap();
+            assert_eq!(backend.requests()[0].messages[0].content, format!("Summarize: {}", "x".repeat(172)));
+        }
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn usage_and_cost_accounting() {
+        let mut context = DefaultContext::new();
+        let models = ModelList::new()
+            .model(ModelSpec::new("big").cost(10.0, 30.0))
+            .model("small")
+            .fallback(Fallback::Downgrade);
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .reply("first").with_usage(1_000, 200)
+            .reply("second").with_usage(2_000, 1_000);
+        let first = PromptVariant::from("First.");
+        let second = PromptVariant::from("Second.");
+        let mut chain = ExecutablePromptChain::<DefaultContext, String>::new();
+        chain.push(first.to_executable(processor::text).models(models.clone()));
+        chain.push(second.to_executable(processor::text).models(models.clone()));
+        let mut flow = chain.flow();
+
+        let result = flow.execute_next(&mut context, &backend).await.unwrap();
+        let step = result.usage().clone();
+        assert_eq!(result.unwrap().as_deref(), Some("first"));
+        assert_eq!(step.calls().iter().map(|call| call.model.as_str()).collect::<Vec<_>>(), ["big", "small"]);
+        assert_eq!(step.calls()[0].tokens, None);
+        assert_eq!(step.model(), Some("small"));
+        assert_eq!(step.tokens(), TokenUsage { prompt_tokens: 1_000, completion_tokens: 200, total_tokens: 1_200 });
+        assert_eq!(step.cost(), 0.0);
+        let prices = PriceTable::new().model("small", 1.0, 2.0);
+        assert!((step.cost_with(&prices) - 0.0014).abs() < 1e-12);
+
+        let result = flow.execute_next(&mut context, &backend).await.unwrap();
+        assert!((result.usage().cost() - 0.05).abs() < 1e-12);
+        let usage = flow.usage();
+        assert_eq!(usage.steps().len(), 2);
+        assert_eq!(usage.tokens(), TokenUsage { prompt_tokens: 3_000, completion_tokens: 1_200, total_tokens: 4_200 });
+        assert!((usage.cost() - 0.05).abs() < 1e-12);
+        assert!((usage.cost_with(&prices) - 0.0514).abs() < 1e-12);
+        assert_eq!(PriceTable::from(&models).get("big"), Some(ModelCost { input: 10.0, output: 30.0 }));
+        assert_eq!(PriceTable::from(&models).get("small"), None);
+    }
+    #[cfg(feature = "executable")]
+    #[tokio::test]
+    async fn model_fallback_chain() {
+        let mut context = DefaultContext::new();
+        let models = ModelList::new()
+            .model("small")
+            .model(ModelSpec::new("primary").priority(10).context_window(8_000).cost(2.5, 10.0).supports_tools(false))
+            .model(ModelSpec::new("no-json").priority(5).supports_json(false))
+            .fallback(Fallback::Downgrade);
+        assert_eq!(models.names(), ["primary", "no-json", "small"]);
+        assert_eq!(models.get(0).unwrap().cost, Some(ModelCost { input: 2.5, output: 10.0 }));
+        let prompt = PromptVariant::from("Hello");
+
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .fail(ApiError::new("This model's maximum context length is 8192 tokens").with_code("context_length_exceeded"))
+            .reply("ok");
+        let executable = prompt.to_executable(processor::text).models(models.clone());
+        let result = executable.execute(&mut context, &backend, None).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(backend.requested_models(), ["primary", "no-json", "small"]);
+
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .reply("ok");
+        let executable = prompt.to_executable(processor::text)
+            .models(models.clone())
+            .response_format(ResponseFormat::JsonObject);
+        executable.execute(&mut context, &backend, None).await.unwrap();
+        assert_eq!(backend.requested_models(), ["primary", "small"]);
+
+        let backend = ScriptedBackend::new().reply("ok");
+        let noop = Tool::new("noop", serde_json::json!({"type": "object"}), |_: serde_json::Value, _: &mut DefaultContext| {
+            Ok::<_, String>(0)
+        });
+        let executable = prompt.to_executable(processor::text).models(models.clone()).tool(noop);
+        executable.execute(&mut context, &backend, None).await.unwrap();
+        assert_eq!(backend.requested_models(), ["no-json"]);
+
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .reply("unused");
+        let executable = prompt.to_executable(processor::text).models(models.clone());
+        let result = executable.execute(&mut context, &backend, Some(2)).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::Backend(BackendError::Api(_))));
+        assert_eq!(backend.requested_models(), ["small"]);
+
+        let backend = ScriptedBackend::new()
+            .api_error("500")
+            .reply("unused");
+        let executable = prompt.to_executable(processor::text).models(models.clone());
+        let result = executable.execute(&mut context, &backend, None).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::Backend(BackendError::Api(_))));
+
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .reply("unused");
+        let executable = prompt.to_executable(processor::text).models(vec!["m1", "m2"]);
+        let result = executable.execute(&mut context, &backend, None).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::Backend(BackendError::Api(_))));
+
+        let backend = ScriptedBackend::new().reply("unused");
+        let executable = prompt.to_executable(processor::text)
+            .models(ModelSpec::new("text-only").supports_json(false))
+            .response_format(ResponseFormat::JsonObject);
+        let result = executable.execute(&mut context, &backend, None).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::NoCapableModel));
+        assert!(backend.requests().is_empty());
+    }
+    #[cfg(feature = "retry")]
+    #[tokio::test(start_paused = true)]
+    async fn circuit_breaker_per_model() {
+        let mut context = DefaultContext::new();
+        let scripted = std::sync::Arc::new(ScriptedBackend::new()
+            .api_error("500")
+            .fail(BackendError::Transport("connection reset".into()))
+            .reply("rerouted")
+            .reply("probed")
+            .api_error("503")
+            .reply("recovered"));
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(2)
+            .cooldown(Duration::from_secs(30));
+        let backend = breaker.wrap(scripted.clone());
+        let prompt = PromptVariant::from("Hello");
+        let primary = prompt.to_executable(processor::text).models("m1");
+        for _ in 0..2 {
+            primary.execute(&mut context, &backend, None).await.unwrap_err();
+        }
+        assert_eq!(breaker.state("m1"), CircuitState::Open);
+        assert_eq!(breaker.state("m2"), CircuitState::Closed);
+        let result = primary.execute(&mut context, &backend, None).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::Backend(BackendError::CircuitOpen { .. })));
+        assert_eq!(scripted.requests().len(), 2);
+
+        let chain = prompt.to_executable(processor::text)
+            .models(ModelList::from(vec!["m1", "m2"]).fallback(Fallback::Downgrade));
+        let result = chain.execute(&mut context, &backend, None).await;
+        assert_eq!(result.unwrap().as_deref(), Some("rerouted"));
+        assert_eq!(scripted.requested_models(), ["m1", "m1", "m2"]);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(breaker.state("m1"), CircuitState::HalfOpen);
+        let result = primary.execute(&mut context, &backend, None).await;
+        assert_eq!(result.unwrap().as_deref(), Some("probed"));
+        assert_eq!(breaker.state("m1"), CircuitState::Closed);
+
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .cooldown(Duration::from_secs(20));
+        let backend = breaker.wrap(scripted.clone());
+        let start = tokio::time::Instant::now();
+        let result = primary.execute_with_retry(&mut context, &backend, None, RetryPolicy::new().jitter(0.0)).await;
+        assert_eq!(result.unwrap().as_deref(), Some("recovered"));
+        assert_eq!(start.elapsed(), Duration::from_secs(20));
+        assert_eq!(scripted.remaining(), 0);
+    }
+    #[cfg(feature = "rate_limit")]
+    #[tokio::test(start_paused = true)]
+    async fn rate_limiter_per_model() {
+        let scripted = std::sync::Arc::new(ScriptedBackend::new()
+            .reply("1")
+            .reply("2")
+            .reply("3")
+            .reply("4").with_usage(10, 90)
+            .reply("5")
+            .reply("6").with_latency(Duration::from_secs(5))
+            .reply("7").with_latency(Duration::from_secs(5)));
+        let limiter = RateLimiter::new()
+            .default_limits(RateLimits::new().max_in_flight(1))
+            .model("fast", RateLimits::new().requests_per_minute(2))
+            .model("big", RateLimits::new().tokens_per_minute(120));
+        let backend = limiter.wrap(scripted.clone());
+        let shared = limiter.wrap(scripted.clone());
+        let mut context = DefaultContext::new();
+        let prompt = PromptVariant::from("Hello");
+
+        let fast = prompt.to_executable(processor::text).models("fast");
+        let start = tokio::time::Instant::now();
+        fast.execute(&mut context, &backend, None).await.unwrap();
+        fast.execute(&mut context, &shared, None).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        fast.execute(&mut context, &backend, None).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(30));
+
+        //"Hello" is 2 tokens, plus the 58 the completion may take
+        let big = prompt.to_executable(processor::text).models("big").max_tokens(58);
+        let start = tokio::time::Instant::now();
+        big.execute(&mut context, &backend, None).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        //100 were used instead of the 60 reserved, the 40 missing for the next request refill in 20 seconds
+        big.execute(&mut context, &backend, None).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(20));
+
+        let other = prompt.to_executable(processor::text).models("other");
+        let mut other_context = DefaultContext::new();
+        let start = tokio::time::Instant::now();
+        let (first, second) = tokio::join!(
+            other.execute(&mut context, &backend, None),
+            other.execute(&mut other_context, &shared, None)
+        );
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert_eq!(scripted.remaining(), 0);
+    }
+    #[cfg(feature = "batch")]
+    #[tokio::test(start_paused = true)]
+    async fn batch_execution() {
+        let backend = ScriptedBackend::new()
+            .reply("John").with_latency(Duration::from_secs(5))
+            .reply("Jane").with_latency(Duration::from_secs(1))
+            .reply("Jim").with_latency(Duration::from_secs(1))
+            .api_error("400")
+            .reply("Joe").with_latency(Duration::from_secs(1));
+        let prompt = PromptVariant::from(PromptTemplate::new("Who is number {a}?").unwrap());
+        let executable = prompt.to_executable(text).models("m");
+        let contexts = (1..=5).map(|a| MyContext {
+            name: String::new(),
+            a,
+            age: String::new()
+        });
+        let mut progress = Vec::new();
+        let start = tokio::time::Instant::now();
+        let items = executable.batch(contexts)
+            .concurrency(2)
+            .on_progress(|batch: &BatchProgress| progress.push((batch.completed, batch.failed, batch.total)))
+            .execute(&backend)
+            .await;
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert_eq!(items.iter().map(|item| item.context.a).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+        let answers = items.iter()
+            .map(|item| item.result.as_ref().ok().cloned().flatten())
+            .collect::<Vec<_>>();
+        assert_eq!(answers, [Some("John".to_string()), Some("Jane".to_string()), Some("Jim".to_string()), None, Some("Joe".to_string())]);
+        assert!(matches!(items[3].result, Err(PromptExecutableError::Backend(BackendError::Api(_)))));
+        assert_eq!(progress, [(1, 0, Some(5)), (2, 0, Some(5)), (3, 1, Some(5)), (4, 1, Some(5)), (5, 1, Some(5))]);
+
+        let backend = ScriptedBackend::new().reply("a").reply("b");
+        let mut contexts = (1..=2).map(|a| MyContext { name: String::new(), a, age: String::new() }).collect::<Vec<_>>();
+        let items = executable.batch(contexts.iter_mut()).execute(&backend).await;
+        assert!(items.iter().all(|item| item.result.is_ok()));
+        assert_eq!(items[1].index, 1);
+    }
+    #[cfg(feature = "retry")]
+    #[tokio::test]
+    async fn retry_rotation_skips_incapable_models() {
+        let mut context = DefaultContext::new();
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .api_error("429")
+            .reply("ok");
+        let noop = Tool::new("noop", serde_json::json!({"type": "object"}), |_: serde_json::Value, _: &mut DefaultContext| {
+            Ok::<_, String>(0)
+        });
+        let prompt = PromptVariant::from("Hello");
+        let executable = prompt.to_executable(processor::text)
+            .models(vec![ModelSpec::new("a").supports_tools(false), ModelSpec::new("b"), ModelSpec::new("c")])
+            .tool(noop);
+        let result = executable.execute_with_retry(&mut context, &backend, None, 3).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(backend.requested_models(), ["b", "c", "b"]);
+    }
+    #[cfg(feature = "retry")]
+    #[tokio::test(start_paused = true)]
+    async fn retry_plain_executables_and_flows() {
+        let mut context = DefaultContext::new(); //holds `Box<dyn Any>`, so neither Send nor Sync
+        let backend = ScriptedBackend::new()
+            .api_error("503")
+            .reply("first")
+            .malformed_response()
+            .reply("second")
+            .api_error("400")
+            .reply("unused");
+        let first = PromptVariant::from("First.");
+        let second = PromptVariant::from("Second.");
+        let mut chain = ExecutablePromptChain::<DefaultContext, String>::new();
+        chain.push(first.to_executable(processor::text).models(vec!["m"]));
+        chain.push(second.to_executable(processor::text).models(vec!["m"]));
+        let policy = RetryPolicy::new().jitter(0.0);
+        let start = tokio::time::Instant::now();
+        let mut flow = chain.flow();
+        let mut answers = Vec::new();
+        while let Some(result) = flow.execute_next_with_retry(&mut context, &backend, policy.clone()).await {
+            answers.push(result.unwrap().unwrap());
+        }
+        assert_eq!(answers, ["first", "second"]);
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(flow.conversation().len(), 4);
+
+        let executable = first.to_executable(processor::text).models(vec!["m"]);
+        let result = executable.execute_with_retry(&mut context, &backend, None, 3).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::Backend(BackendError::Api(_))));
+        let result = executable.execute_with_retry(&mut context, &backend, Some(4), 3).await;
+        assert_eq!(result.unwrap().as_deref(), Some("unused"));
+    }
+    #[cfg(feature = "timeout")]
+    #[tokio::test(start_paused = true)]
+    async fn timeouts_and_cancellation() {
+        let mut context = DefaultContext::new();
+        let backend = ScriptedBackend::new()
+            .reply("late").with_latency(Duration::from_secs(5))
+            .reply("first").with_latency(Duration::from_secs(4))
+            .reply("second").with_latency(Duration::from_secs(4))
+            .reply("third").with_latency(Duration::from_secs(4));
+        let step = PromptVariant::from("Step.");
+        let executable = step.to_executable(processor::text).models("m").timeout(Duration::from_secs(2));
+        let start = tokio::time::Instant::now();
+        let result = executable.execute(&mut context, &backend, None).await;
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::Timeout(timeout) if timeout == Duration::from_secs(2)));
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+        let mut chain = ExecutablePromptChain::<DefaultContext, String>::new().with_timeout(Duration::from_secs(10));
+        for _ in 0..4 {
+            chain.push(step.to_executable(processor::text).models("m"));
+        }
+        let start = tokio::time::Instant::now();
+        let mut flow = chain.flow();
+        assert_eq!(flow.execute_next(&mut context, &backend).await.unwrap().unwrap().as_deref(), Some("first"));
+        assert_eq!(flow.execute_next(&mut context, &backend).await.unwrap().unwrap().as_deref(), Some("second"));
+        let result = flow.execute_next(&mut context, &backend).await.unwrap();
+        assert!(matches!(result.unwrap_err(), PromptExecutableError::Timeout(timeout) if timeout == Duration::from_secs(10)));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert!(flow.is_halted());
+        assert!(flow.execute_next(&mut context, &backend).await.is_none());
+        assert_eq!(flow.conversation().len(), 4);
+
+        let backend = ScriptedBackend::new().reply("never").with_latency(Duration::from_secs(5));
+        let mut chain = ExecutablePromptChain::<DefaultContext, String>::new();
+        chain.push(step.to_executable(processor::text).models("m"));
+        chain.push(step.to_executable(processor::text).models("m"));
+        let cancel = CancellationToken::new();
+        let mut flow = chain.flow().with_cancellation(cancel.clone());
+        let start = tokio::time::Instant::now();
+        let (result, _) = tokio::join!(flow.execute_next(&mut context, &backend), async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            cancel.cancel();
+        });
+        assert!(matches!(result.unwrap().unwrap_err(), PromptExecutableError::Cancelled));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert!(flow.execute_next(&mut context, &backend).await.is_none());
+        assert!(flow.conversation().is_empty());
+
+        #[cfg(feature = "retry")]
+        {
+            let backend = ScriptedBackend::new()
+                .reply("late").with_latency(Duration::from_secs(5))
+                .reply("on time").with_latency(Duration::from_secs(1));
+            let start = tokio::time::Instant::now();
+            let result = executable.execute_with_retry(&mut context, &backend, None, RetryPolicy::new().jitter(0.0)).await;
+            assert_eq!(result.unwrap().as_deref(), Some("on time"));
+            assert_eq!(start.elapsed(), Duration::from_secs(4));
+        }
+    }
+    #[cfg(all(feature = "retry", feature = "send", feature = "timeout"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_executable_timeout() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .reply("late").with_latency(Duration::from_secs(5))
+            .reply("on time").with_latency(Duration::from_secs(1));
+        let step = SendPromptVariant::from("Step.");
+        let executable = step.to_retry_executable(processor::text)
+            .models("m")
+            .timeout(Duration::from_secs(2));
+        assert_eq!(executable.get_timeout(), Some(Duration::from_secs(2)));
+        let start = tokio::time::Instant::now();
+        let result = executable
+            .execute_with_retry(&mut my_context, &backend, None)
+            .await
+            .retry(RetryPolicy::new().jitter(0.0))
+            .await;
+        assert_eq!(result.unwrap().as_deref(), Some("on time"));
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+        assert_eq!(backend.remaining(), 0);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_honors_server_wait_hint() {
+        let backend = ScriptedBackend::new()
+            .rate_limited(Duration::from_secs(7))
+            .rate_limited(Duration::from_secs(90))
+            .reply("\"ok\"");
+        let start = tokio::time::Instant::now();
+        let policy = RetryPolicy::new().max_delay(Duration::from_secs(20));
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, policy).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(start.elapsed(), Duration::from_secs(27));
+
+        let error = ApiError::new("slow down").with_headers([
+            ("Retry-After", "12"),
+            ("x-ratelimit-reset-requests", "1s"),
+        ]);
+        assert_eq!(error.retry_after, Some(Duration::from_secs(12)));
+        let error = ApiError::new("slow down").with_headers([("retry-after-ms", "1500")]);
+        assert_eq!(error.retry_after, Some(Duration::from_millis(1500)));
+        let error = ApiError::new("slow down").with_headers([("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]);
+        assert_eq!(error.retry_after, Some(Duration::ZERO));
+        let error = ApiError::new("slow down").with_headers([
+            ("x-ratelimit-reset-requests", "6m0s"),
+            ("x-ratelimit-reset-tokens", "20ms"),
+            ("content-type", "application/json"),
+        ]);
+        assert_eq!(error.retry_after, Some(Duration::from_secs(360)));
+        let error = ApiError::new("slow down").with_headers([("retry-after", "soon")]);
+        assert_eq!(error.retry_after, None);
+    }
+    #[cfg(feature = "executable")]
+    #[test]
+    fn wait_hint_from_message() {
+        use crate::feature::executor::wait_hint::from_message;
+        let hint = from_message("Rate limit reached for gpt-4o. Please try again in 1m2.5s. Visit ...");
+        assert_eq!(hint, Some(Duration::from_millis(62500)));
+        assert_eq!(from_message("Please try again in 20ms."), Some(Duration::from_millis(20)));
+        assert_eq!(from_message("Rate limit reached"), None);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_policy_rules_and_classifier() {
+        let backend = ScriptedBackend::new()
+            .api_error("400")
+            .filtered()
+            .reply("\"ok\"");
+        let policy = RetryPolicy::new()
+            .rule(ErrorClass::Rejected, RetryRule::Immediately)
+            .classifier(|error| match error {
+                PromptExecutableError::Backend(BackendError::Api(e)) if e.matches_status(400) => Some(ErrorClass::Transient),
+                _ => None,
+            });
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, policy).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+
+        let backend = ScriptedBackend::new()
+            .api_error("429")
+            .reply("\"unused\"");
+        let policy = RetryPolicy::new().rule(ErrorClass::RateLimit, RetryRule::GiveUp);
+        let result = scripted_retry(&backend, "Hello", vec!["m1", "m2"], None, policy).await;
+        assert!(matches!(result, Err(PromptExecutableError::Backend(BackendError::Api(_)))));
+        assert_eq!(backend.remaining(), 1);
+
+        assert!(ApiError::new("Server error").with_code("500").matches_status(500));
+        assert!(!ApiError::new("Server error").with_code("5000").matches_status(500));
+        assert!(!ApiError::new("Server error").with_code("err_500_db").matches_status(500));
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_executable_in_conversation() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let mut conversation = Conversation::new().with_system("sys");
+        conversation.push(ChatMessage::user("I'm John."));
+        conversation.push(ChatMessage::assistant("Hi John"));
+        let backend = ScriptedBackend::new()
+            .api_error("500")
+            .reply("You are 18");
+        let ask = SendPromptVariant::from("How old am I?");
+        let result = ask.to_retry_executable(processor::text)
+            .models("m")
+            .execute_in_with_retry(&mut my_context, &backend, None, &mut conversation)
+            .await
+            .retry(RetryPolicy::new().initial_delay(Duration::ZERO))
+            .await;
+        assert_eq!(result.unwrap().as_deref(), Some("You are 18"));
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].messages.len(), 4);
+        assert_eq!(conversation.messages()[3..], [ChatMessage::user("How old am I?"), ChatMessage::assistant("You are 18")]);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_executable_usage() {
+        let mut my_context = MyContext {
+            name: "John".to_string(),
+            a: 1,
+            age: "18".to_string()
+        };
+        let backend = ScriptedBackend::new()
+            .api_error("500")
+            .reply("ok").with_usage(10, 5);
+        let prompt = SendPromptVariant::from("Hello");
+        let first = prompt.to_retry_executable(processor::text)
+            .models(ModelSpec::new("m").cost(1.0, 2.0))
+            .execute_with_retry(&mut my_context, &backend, None)
+            .await;
+        assert!(first.is_err());
+        assert_eq!(first.usage().calls().len(), 1);
+        let result = first.retry(RetryPolicy::new().initial_delay(Duration::ZERO)).await;
+        let usage = result.usage().clone();
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(usage.calls().iter().map(|call| call.model.as_str()).collect::<Vec<_>>(), ["m", "m"]);
+        assert_eq!(usage.tokens(), TokenUsage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 });
+        assert!((usage.cost() - 0.00002).abs() < 1e-12);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_stops_on_fatal_api_error() {
+        let backend = ScriptedBackend::new()
+            .api_error("400")
+            .reply("\"unused\"");
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, 3).await;
+        assert!(matches!(result, Err(PromptExecutableError::Backend(BackendError::Api(_)))));
+        assert_eq!(backend.remaining(), 1);
+
+        let backend = ScriptedBackend::new()
+            .api_error("400")
+            .reply("\"unused\"");
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, RetryPolicy::new().max_retries(0)).await;
+        assert!(matches!(result, Err(PromptExecutableError::Backend(BackendError::Api(_)))));
+        assert_eq!(backend.remaining(), 1);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_deserialize_error() {
+        let backend = ScriptedBackend::new()
+            .reply("not json")
+            .reply("\"ok\"");
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, 3).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test]
+    async fn retry_processor_errors() {
+        let backend = ScriptedBackend::new()
+            .reply("   ")
+            .truncated("\"o")
+            .refuse("I can't help with that.")
+            .reply("\"ok\"");
+        let result = scripted_retry(&backend, "Hello", vec!["m1", "m2"], None, 3).await;
+        assert_eq!(result.unwrap().as_deref(), Some("ok"));
+        assert_eq!(backend.requested_models(), vec!["m1", "m1", "m2", "m1"]);
+
+        let backend = ScriptedBackend::new()
+            .filtered()
+            .reply("\"ok\"");
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, 3).await;
+        assert!(matches!(result, Err(PromptExecutableError::Processor(ProcessorError::ContentFilter))));
+        assert_eq!(backend.remaining(), 1);
+    }
+    #[cfg(all(feature = "retry", feature = "send"))]
+    #[tokio::test(start_paused = true)]
+    async fn retry_gives_up() {
+        let backend = ScriptedBackend::new()
+            .api_error("500")
+            .api_error("500")
+            .api_error("500");
+        let result = scripted_retry(&backend, "Hello", vec!["m1"], None, 2).await;
+        assert!(matches!(result, Err(PromptExecutableError::RetryFail(_))));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "gave up after running out of retries or time, last error: backend error: api error: scripted api error (code: 500)"
+        );
+        assert_eq!(backend.remaining(), 0);
     }
 }
@@ -3,3 +3,6 @@ pub mod context;
 pub mod error;
 pub mod control;
 pub mod template;
+pub mod filter;
+pub mod value;
+pub mod role;
@@ -1,12 +1,31 @@
 use std::any::Any;
 use std::collections::HashMap;
-use std::fmt::Display;
+use crate::prompt::value::TemplateValue;
 
 pub trait Context {
     fn get<T: 'static>(&self, key: &str) -> Option<&T>;
     fn get_mut<T: 'static>(&mut self, key: &str) -> Option<&mut T>;
+    //Returns false when the context cannot hold values, which is the default
+    fn set<T: 'static>(&mut self, _key: &str, _value: T) -> bool {
+        false
+    }
 
+    //Text for `{key}` in templates, contexts with structured values override `template_value` as well
     fn template_var(&self, key: &str) -> Option<String>;
+    fn template_value(&self, key: &str) -> Option<TemplateValue> {
+        self.template_var(key).map(TemplateValue::String)
+    }
+    //Variables every context of the type provides, for checks without an instance at hand
+    fn template_var_names() -> Vec<&'static str> {
+        Vec::new()
+    }
+}
+
+//Values stored as a `TemplateValue` or a `String` are visible to templates
+fn stored_value(value: &dyn Any) -> Option<TemplateValue> {
+    value.downcast_ref::<TemplateValue>()
+        .cloned()
+        .or_else(|| value.downcast_ref::<String>().cloned().map(TemplateValue::String))
 }
 
 impl Context for HashMap<String, Box<dyn Any>> {
@@ -16,8 +35,15 @@ impl Context for HashMap<String, Box<dyn Any>> {
     fn get_mut<T: 'static>(&mut self, key: &str) -> Option<&mut T> {
         self.get_mut(key).and_then(|v| v.downcast_mut())
     }
+    fn set<T: 'static>(&mut self, key: &str, value: T) -> bool {
+        self.insert(key.to_string(), Box::new(value));
+        true
+    }
     fn template_var(&self, key: &str) -> Option<String> {
-        None
+        Context::template_value(self, key).map(|value| value.to_string())
+    }
+    fn template_value(&self, key: &str) -> Option<TemplateValue> {
+        self.get(key).and_then(|value| stored_value(&**value))
     }
 }
 impl Context for () {
@@ -51,7 +77,14 @@ impl Context for DefaultContext {
     fn get_mut<T: 'static>(&mut self, key: &str) -> Option<&mut T> {
         self.data.get_mut(key).and_then(|v| v.downcast_mut())
     }
+    fn set<T: 'static>(&mut self, key: &str, value: T) -> bool {
+        self.insert(key.to_string(), value);
+        true
+    }
     fn template_var(&self, key: &str) -> Option<String> {
-        None
+        self.template_value(key).map(|value| value.to_string())
+    }
+    fn template_value(&self, key: &str) -> Option<TemplateValue> {
+        self.data.get(key).and_then(|value| stored_value(&**value))
     }
 }
\ No newline at end of file
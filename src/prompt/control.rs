@@ -1,7 +1,9 @@
 use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 use crate::prompt::context::Context;
 use crate::prompt::error::{IfPromptBuilderError, PromptError};
 use crate::prompt::naive::{Prompt, PromptVariant};
+use crate::prompt::role::PromptMessage;
 
 pub struct IfPrompt<'a, C>
 where
@@ -15,10 +17,10 @@ impl<'a, C> IfPrompt<'a, C>
 where
     C: Context
 {
-    pub fn get_then(&self) -> &PromptVariant<C> {
+    pub fn get_then(&self) -> &PromptVariant<'_, C> {
         &self.then
     }
-    pub fn get_otherwise(&self) -> Option<&PromptVariant<C>> {
+    pub fn get_otherwise(&self) -> Option<&PromptVariant<'_, C>> {
         self.otherwise.as_ref()
     }
     pub fn get_condition(&self) -> &dyn Fn(&C) -> bool {
@@ -36,16 +38,45 @@ impl<C> Prompt<C> for IfPrompt<'_, C>
 where
     C: Context
 {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError> {
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt_str_with(context, &HashMap::new())
+    }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt_messages_with(context, &HashMap::new())
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
+        if (self.condition)(context) {
+            self.then.prompt_str_with(context, overrides)
+        } else if let Some(otherwise) = self.otherwise.as_ref() {
+            otherwise.prompt_str_with(context, overrides)
+        } else {
+            Ok(None)
+        }
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
         if (self.condition)(context) {
-            self.then.prompt_str(context)
+            self.then.prompt_messages_with(context, overrides)
+        } else if let Some(otherwise) = self.otherwise.as_ref() {
+            otherwise.prompt_messages_with(context, overrides)
         } else {
-            if let Some(otherwise) = self.otherwise.as_ref() {
-                otherwise.prompt_str(context)
-            }
-            else {
-                Ok(None)
-            }
+            Ok(Vec::new())
+        }
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        let mut variables = self.then.variables();
+        variables.extend(self.otherwise.iter().flat_map(|otherwise| otherwise.variables()));
+        variables
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        let mut variables = self.then.required_variables();
+        variables.extend(self.otherwise.iter().flat_map(|otherwise| otherwise.required_variables()));
+        variables
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        if (self.condition)(context) {
+            self.then.missing_variables(context)
+        } else {
+            self.otherwise.as_ref().map(|otherwise| otherwise.missing_variables(context)).unwrap_or_default()
         }
     }
 }
@@ -60,7 +91,7 @@ impl<'a, C> LoopPrompt<'a, C>
 where
     C: Context,
 {
-    pub fn get_prompt(&self) -> &PromptVariant<C> {
+    pub fn get_prompt(&self) -> &PromptVariant<'_, C> {
         &self.prompt
     }
     pub fn get_condition(&self) -> &dyn Fn(&C) -> bool {
@@ -77,9 +108,31 @@ impl<'a, C> Prompt<C> for LoopPrompt<'a, C>
 where
     C: Context,
 {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError> {
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
         self.prompt.prompt_str(context)
     }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt.prompt_messages(context)
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt.prompt_str_with(context, overrides)
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt.prompt_messages_with(context, overrides)
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        self.prompt.variables()
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        self.prompt.required_variables()
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        if (self.condition)(context) {
+            self.prompt.missing_variables(context)
+        } else {
+            Vec::new()
+        }
+    }
 }
 
 //Builders
@@ -1,18 +1,157 @@
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum PromptError {
     #[error("Missing variable in context: {0}.")]
     MissingContextVar(String),
+    #[error("Missing variables in context: {}.", .0.join(", "))]
+    MissingContextVars(Vec<String>),
+    #[error("Filter `{filter}` failed: {message}.")]
+    FilterFailed { filter: String, message: String },
+    #[error("Cannot loop over {0}.")]
+    NotIterable(String),
     #[error("Fail to format.")]
     FailToFormatTemplate(#[from] PromptTemplateError)
 }
-#[derive(Debug, Error)]
-pub enum PromptTemplateError {
-    #[error("Brace Mismatch in prompt template.")]
-    BraceMismatch,
-    #[error("Empty variable in prompt template.")]
-    EmptyVariable
+//Where and why a template failed to parse, `diagnostic` renders it the way compilers do
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid prompt template at line {}, column {}: {}.", .0.line, .0.column, .0.kind)]
+pub struct PromptTemplateError(Box<TemplateErrorDetails>); //NOTE: boxed to keep `PromptError` small
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TemplateErrorDetails {
+    kind: TemplateErrorKind,
+    line: usize, //from 1
+    column: usize, //from 1, in characters
+    width: usize, //characters the problem spans on its line
+    source_line: String,
+    help: Option<String>,
+    origin: Option<String>,
+}
+impl PromptTemplateError {
+    pub(crate) fn new(kind: TemplateErrorKind, template: &str, span: Range<usize>) -> Self {
+        let line_start = template[..span.start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = template[span.start..].find('\n').map_or(template.len(), |index| span.start + index);
+        let help = match &kind {
+            TemplateErrorKind::UnclosedBlock(block) => Some(format!("close it with `{{% end{block} %}}`")),
+            _ => None,
+        };
+        PromptTemplateError(Box::new(TemplateErrorDetails {
+            kind,
+            line: template[..span.start].matches('\n').count() + 1,
+            column: template[line_start..span.start].chars().count() + 1,
+            width: template[span.start..span.end.min(line_end)].chars().count().max(1),
+            source_line: template[line_start..line_end].trim_end_matches('\r').to_string(),
+            help,
+            origin: None,
+        }))
+    }
+    pub(crate) fn with_help(mut self, help: String) -> Self {
+        self.0.help = Some(help);
+        self
+    }
+    //Names where the template came from, a file path for instance, in the diagnostic
+    pub fn with_origin(mut self, origin: impl Into<String>) -> Self {
+        self.0.origin = Some(origin.into());
+        self
+    }
+    pub fn kind(&self) -> &TemplateErrorKind {
+        &self.0.kind
+    }
+    pub fn line(&self) -> usize {
+        self.0.line
+    }
+    pub fn column(&self) -> usize {
+        self.0.column
+    }
+    pub fn source_line(&self) -> &str {
+        &self.0.source_line
+    }
+    pub fn help(&self) -> Option<&str> {
+        self.0.help.as_deref()
+    }
+    //error: unknown filter `shout`
+    // --> greeting.txt:1:13
+    //  |
+    //1 | Hello {name|shout}!
+    //  |             ^^^^^ not a registered filter
+    pub fn diagnostic(&self) -> String {
+        let gutter = " ".repeat(self.0.line.to_string().len());
+        //Tabs are kept so the caret lines up with what the line shows
+        let indent = self.0.source_line.chars()
+            .take(self.0.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let mut diagnostic = format!(
+            "error: {}\n{gutter}--> {}:{}:{}\n{gutter} |\n{} | {}\n{gutter} | {indent}{} {}",
+            self.0.kind,
+            self.0.origin.as_deref().unwrap_or("template"),
+            self.0.line,
+            self.0.column,
+            self.0.line,
+            self.0.source_line,
+            "^".repeat(self.0.width),
+            self.0.kind.label(),
+        );
+        if let Some(help) = &self.0.help {
+            diagnostic.push_str(&format!("\n{gutter} |\n{gutter} = help: {help}"));
+        }
+        diagnostic
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateErrorKind {
+    #[error("unclosed `{{`")]
+    UnclosedBrace,
+    #[error("stray `}}`")]
+    StrayBrace,
+    #[error("nested `{{` in a variable")]
+    NestedBrace,
+    #[error("empty variable")]
+    EmptyVariable,
+    #[error("invalid identifier `{0}`")]
+    InvalidIdentifier(String),
+    #[error("invalid variable path `{0}`")]
+    InvalidPath(String),
+    #[error("empty filter after variable `{0}`")]
+    EmptyFilter(String),
+    #[error("unknown filter `{0}`")]
+    UnknownFilter(String),
+    #[error("invalid arguments for filter `{filter}`: {message}")]
+    InvalidFilterArgs { filter: String, message: String },
+    #[error("unterminated string")]
+    UnterminatedString,
+    #[error("unterminated tag")]
+    UnterminatedTag,
+    #[error("unknown tag `{0}`")]
+    UnknownTag(String),
+    #[error("invalid tag `{0}`")]
+    InvalidTag(String),
+    #[error("unexpected `{0}`")]
+    UnexpectedTag(String),
+    #[error("unclosed `{0}` block")]
+    UnclosedBlock(String),
+}
+impl TemplateErrorKind {
+    fn label(&self) -> &'static str {
+        match self {
+            TemplateErrorKind::UnclosedBrace => "this `{` is never closed on its line",
+            TemplateErrorKind::StrayBrace => "no `{` to close, write `}}` for a literal brace",
+            TemplateErrorKind::NestedBrace => "variables cannot hold braces, write `{{` outside them for a literal brace",
+            TemplateErrorKind::EmptyVariable => "expected a variable name",
+            TemplateErrorKind::InvalidIdentifier(_) => "names are made of letters, digits and `_`",
+            TemplateErrorKind::InvalidPath(_) => "expected `[index]` with a number",
+            TemplateErrorKind::EmptyFilter(_) => "expected a filter name after `|`",
+            TemplateErrorKind::UnknownFilter(_) => "not a registered filter",
+            TemplateErrorKind::InvalidFilterArgs { .. } => "arguments rejected by the filter",
+            TemplateErrorKind::UnterminatedString => "this `\"` is never closed",
+            TemplateErrorKind::UnterminatedTag => "this `{%` is never closed with `%}`",
+            TemplateErrorKind::UnknownTag(_) => "expected `if`, `else`, `endif`, `for` or `endfor`",
+            TemplateErrorKind::InvalidTag(_) => "expected `if [not] name`, `for item in list`, `else`, `endif` or `endfor`",
+            TemplateErrorKind::UnexpectedTag(_) => "no open block it belongs to",
+            TemplateErrorKind::UnclosedBlock(_) => "this block is never closed",
+        }
+    }
 }
 #[derive(Debug, Error)]
 pub enum ControlPromptBuilderError {
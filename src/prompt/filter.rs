@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, LazyLock};
+use crate::prompt::value::TemplateValue;
+
+//Transforms a variable's value in a template, as in `{name|truncate:200}`
+pub trait TemplateFilter: Send + Sync {
+    //Checked once when the template is parsed, an error fails the parse
+    fn check(&self, _args: &[String]) -> Result<(), String> {
+        Ok(())
+    }
+    fn apply(&self, value: &str, args: &[String]) -> Result<String, String>;
+    //Filters that care about structure, like `join` on a list, override this, the others see the value as text
+    fn apply_value(&self, value: &TemplateValue, args: &[String]) -> Result<String, String> {
+        self.apply(&value.to_string(), args)
+    }
+    //What a missing variable turns into, it stays missing by default
+    fn missing(&self, _args: &[String]) -> Option<String> {
+        None
+    }
+}
+impl<F> TemplateFilter for F
+where
+    F: Fn(&str, &[String]) -> Result<String, String> + Send + Sync
+{
+    fn apply(&self, value: &str, args: &[String]) -> Result<String, String> {
+        self(value, args)
+    }
+}
+
+fn expect_args(args: &[String], count: usize) -> Result<(), String> {
+    match (count, args.len()) {
+        (expected, given) if expected == given => Ok(()),
+        (0, given) => Err(format!("takes no argument, {given} given")),
+        (1, given) => Err(format!("takes one argument, {given} given")),
+        (expected, given) => Err(format!("takes {expected} arguments, {given} given")),
+    }
+}
+
+struct Upper;
+impl TemplateFilter for Upper {
+    fn check(&self, args: &[String]) -> Result<(), String> {
+        expect_args(args, 0)
+    }
+    fn apply(&self, value: &str, _args: &[String]) -> Result<String, String> {
+        Ok(value.to_uppercase())
+    }
+}
+
+struct Lower;
+impl TemplateFilter for Lower {
+    fn check(&self, args: &[String]) -> Result<(), String> {
+        expect_args(args, 0)
+    }
+    fn apply(&self, value: &str, _args: &[String]) -> Result<String, String> {
+        Ok(value.to_lowercase())
+    }
+}
+
+struct Trim;
+impl TemplateFilter for Trim {
+    fn check(&self, args: &[String]) -> Result<(), String> {
+        expect_args(args, 0)
+    }
+    fn apply(&self, value: &str, _args: &[String]) -> Result<String, String> {
+        Ok(value.trim().to_string())
+    }
+}
+
+//`truncate:200` keeps the first 200 characters, `truncate:200,"..."` marks the cut
+struct Truncate;
+impl TemplateFilter for Truncate {
+    fn check(&self, args: &[String]) -> Result<(), String> {
+        match args {
+            [length] | [length, _] => length.parse::<usize>()
+                .map(|_| ())
+                .map_err(|_| format!("length must be a number, got `{length}`")),
+            _ => Err(format!("takes a length and an optional marker, {} arguments given", args.len())),
+        }
+    }
+    fn apply(&self, value: &str, args: &[String]) -> Result<String, String> {
+        let length = args[0].parse::<usize>().map_err(|e| e.to_string())?;
+        match value.char_indices().nth(length) {
+            Some((end, _)) => Ok(format!("{}{}", &value[..end], args.get(1).map_or("", String::as_str))),
+            None => Ok(value.to_string()),
+        }
+    }
+}
+
+//Joins the items of a list, or the lines of any other value
+struct Join;
+impl TemplateFilter for Join {
+    fn check(&self, args: &[String]) -> Result<(), String> {
+        expect_args(args, 1)
+    }
+    fn apply(&self, value: &str, args: &[String]) -> Result<String, String> {
+        Ok(value.lines().collect::<Vec<_>>().join(&args[0]))
+    }
+    fn apply_value(&self, value: &TemplateValue, args: &[String]) -> Result<String, String> {
+        match value {
+            TemplateValue::List(items) => Ok(items.iter().map(TemplateValue::to_string).collect::<Vec<_>>().join(&args[0])),
+            value => self.apply(&value.to_string(), args),
+        }
+    }
+}
+
+//Makes the value safe to place between the quotes of a JSON string
+struct JsonEscape;
+impl TemplateFilter for JsonEscape {
+    fn check(&self, args: &[String]) -> Result<(), String> {
+        expect_args(args, 0)
+    }
+    fn apply(&self, value: &str, _args: &[String]) -> Result<String, String> {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                c if c.is_control() => {
+                    let _ = write!(escaped, "\\u{:04x}", c as u32);
+                },
+                c => escaped.push(c),
+            }
+        }
+        Ok(escaped)
+    }
+}
+
+//Stands in for a missing or empty value
+struct DefaultValue;
+impl TemplateFilter for DefaultValue {
+    fn check(&self, args: &[String]) -> Result<(), String> {
+        expect_args(args, 1)
+    }
+    fn apply(&self, value: &str, args: &[String]) -> Result<String, String> {
+        Ok(if value.is_empty() { args[0].clone() } else { value.to_string() })
+    }
+    fn missing(&self, args: &[String]) -> Option<String> {
+        Some(args[0].clone())
+    }
+}
+
+static BUILTIN: LazyLock<FilterRegistry> = LazyLock::new(|| FilterRegistry {
+    filters: HashMap::from([
+        ("upper".to_string(), Arc::new(Upper) as Arc<dyn TemplateFilter>),
+        ("lower".to_string(), Arc::new(Lower)),
+        ("trim".to_string(), Arc::new(Trim)),
+        ("truncate".to_string(), Arc::new(Truncate)),
+        ("join".to_string(), Arc::new(Join)),
+        ("json_escape".to_string(), Arc::new(JsonEscape)),
+        ("default".to_string(), Arc::new(DefaultValue)),
+    ]),
+});
+
+//Filters a template may use, the built-in ones included.
+//Filters are looked up when the template is parsed, so changing the registry afterwards does not affect it.
+#[derive(Clone)]
+pub struct FilterRegistry {
+    filters: HashMap<String, Arc<dyn TemplateFilter>>,
+}
+impl Default for FilterRegistry {
+    fn default() -> Self {
+        BUILTIN.clone()
+    }
+}
+impl FilterRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    //Replaces any filter of the same name, built-in ones included
+    pub fn filter(mut self, name: impl Into<String>, filter: impl TemplateFilter + 'static) -> Self {
+        self.filters.insert(name.into(), Arc::new(filter));
+        self
+    }
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn TemplateFilter>> {
+        self.filters.get(name)
+    }
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.filters.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+    pub(crate) fn builtin() -> &'static FilterRegistry {
+        &BUILTIN
+    }
+}
@@ -1,21 +1,71 @@
 use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 
 use crate::prompt::context::{Context};
 use crate::prompt::control::{IfPrompt, LoopPrompt};
 use crate::prompt::error::PromptError;
+use crate::prompt::role::{ComposedPrompt, PromptMessage, Role, RolePart};
 use crate::prompt::template::PromptTemplate;
 
-#[cfg(feature = "async_oai")]
-use async_openai::types::CreateChatCompletionResponse;
-#[cfg(feature = "async_oai")]
-use serde::Deserialize;
-#[cfg(feature = "async_oai")]
-use async_openai::Client;
-#[cfg(feature = "async_oai")]
-use async_openai::config::OpenAIConfig;
-
 pub trait Prompt<C: Context> {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError>;
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError>;
+    //Plain prompts are a single user message
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        Ok(self.prompt_str(context)?
+            .map(|content| PromptMessage::new(Role::User, content))
+            .into_iter()
+            .collect())
+    }
+    //Renders with the given template variables taking the place of the context's,
+    //prompts without variables can leave the defaults
+    fn prompt_str_with(&self, context: &C, _overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt_str(context)
+    }
+    fn prompt_messages_with(&self, context: &C, _overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt_messages(context)
+    }
+    //Template variables the prompt reads, loop items aside
+    fn variables(&self) -> BTreeSet<&str> {
+        BTreeSet::new()
+    }
+    //The variables a context has to provide, those only tested by conditions or given a fallback such as `default` may be missing
+    fn required_variables(&self) -> BTreeSet<&str> {
+        BTreeSet::new()
+    }
+    //Every variable missing from the context for what the prompt would render with it now
+    fn missing_variables(&self, _context: &C) -> Vec<String> {
+        Vec::new()
+    }
+    fn validate(&self, context: &C) -> Result<(), PromptError> {
+        check_missing(self.missing_variables(context))
+    }
+    //Checks the variables every context of the type provides, see `Context::template_var_names`
+    fn validate_type(&self) -> Result<(), PromptError> {
+        check_missing(undeclared::<C>(self.required_variables()))
+    }
+}
+
+pub(crate) fn check_missing(missing: Vec<String>) -> Result<(), PromptError> {
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(PromptError::MissingContextVars(missing))
+    }
+}
+pub(crate) fn undeclared<C: Context>(required: BTreeSet<&str>) -> Vec<String> {
+    let declared = C::template_var_names();
+    required.into_iter()
+        .filter(|name| !declared.contains(name))
+        .map(str::to_string)
+        .collect()
+}
+//Adds the names not listed yet, in the order they were found
+pub(crate) fn merge_missing(missing: &mut Vec<String>, found: Vec<String>) {
+    for name in found {
+        if !missing.contains(&name) {
+            missing.push(name);
+        }
+    }
 }
 
 pub enum PromptVariant<'a, C>
@@ -26,6 +76,7 @@ where
     Template(PromptTemplate),
     If(Box<IfPrompt<'a, C>>),
     Loop(Box<LoopPrompt<'a, C>>),
+    Composed(Box<ComposedPrompt<'a, C>>),
 }
 impl<'a, C: Context> PromptVariant<'a, C> {
     pub fn naive(s: Cow<'a, str>) -> Self {
@@ -40,6 +91,12 @@ impl<'a, C: Context> PromptVariant<'a, C> {
     pub fn template(t: PromptTemplate) -> Self {
         PromptVariant::Template(t)
     }
+    pub fn composed(p: ComposedPrompt<'a, C>) -> Self {
+        PromptVariant::Composed(Box::new(p))
+    }
+    pub fn with_role(self, role: Role) -> Self {
+        PromptVariant::composed(RolePart::new(role, self).into())
+    }
 }
 impl<'a, C: Context> From<String> for PromptVariant<'a, C> {
     fn from(s: String) -> Self {
@@ -71,13 +128,68 @@ impl<'a, C: Context> From<LoopPrompt<'a, C>> for PromptVariant<'a, C> {
         PromptVariant::loop_prompt(p)
     }
 }
+impl<'a, C: Context> From<ComposedPrompt<'a, C>> for PromptVariant<'a, C> {
+    fn from(p: ComposedPrompt<'a, C>) -> Self {
+        PromptVariant::composed(p)
+    }
+}
+impl<'a, C: Context> From<RolePart<PromptVariant<'a, C>>> for PromptVariant<'a, C> {
+    fn from(p: RolePart<PromptVariant<'a, C>>) -> Self {
+        PromptVariant::composed(p.into())
+    }
+}
 impl<C: Context> Prompt<C> for PromptVariant<'_, C> {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError> {
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt_str_with(context, &HashMap::new())
+    }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt_messages_with(context, &HashMap::new())
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
         match self {
             PromptVariant::Naive(s) => Ok(Some(Cow::Borrowed(s))), //Is this right?
-            PromptVariant::Template(p) => p.prompt_str(context),
-            PromptVariant::If(p) => p.prompt_str(context),
-            PromptVariant::Loop(p) => p.prompt_str(context),
+            PromptVariant::Template(p) => p.prompt_str_with(context, overrides),
+            PromptVariant::If(p) => p.prompt_str_with(context, overrides),
+            PromptVariant::Loop(p) => p.prompt_str_with(context, overrides),
+            PromptVariant::Composed(p) => p.prompt_str_with(context, overrides),
+        }
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        match self {
+            PromptVariant::If(p) => p.prompt_messages_with(context, overrides),
+            PromptVariant::Loop(p) => p.prompt_messages_with(context, overrides),
+            PromptVariant::Composed(p) => p.prompt_messages_with(context, overrides),
+            _ => Ok(self.prompt_str_with(context, overrides)?
+                .map(|content| PromptMessage::new(Role::User, content))
+                .into_iter()
+                .collect()),
+        }
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        match self {
+            PromptVariant::Naive(_) => BTreeSet::new(),
+            PromptVariant::Template(p) => Prompt::<C>::variables(p),
+            PromptVariant::If(p) => p.variables(),
+            PromptVariant::Loop(p) => p.variables(),
+            PromptVariant::Composed(p) => p.variables(),
+        }
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        match self {
+            PromptVariant::Naive(_) => BTreeSet::new(),
+            PromptVariant::Template(p) => Prompt::<C>::required_variables(p),
+            PromptVariant::If(p) => p.required_variables(),
+            PromptVariant::Loop(p) => p.required_variables(),
+            PromptVariant::Composed(p) => p.required_variables(),
+        }
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        match self {
+            PromptVariant::Naive(_) => Vec::new(),
+            PromptVariant::Template(p) => p.missing_variables(context),
+            PromptVariant::If(p) => p.missing_variables(context),
+            PromptVariant::Loop(p) => p.missing_variables(context),
+            PromptVariant::Composed(p) => p.missing_variables(context),
         }
     }
 }
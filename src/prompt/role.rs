@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use crate::prompt::context::Context;
+use crate::prompt::error::PromptError;
+use crate::prompt::naive::{merge_missing, Prompt, PromptVariant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    System,
+    Developer,
+    User,
+    Assistant, //as the last message, a prefill the model continues from
+    Tool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptMessage<'a> {
+    pub role: Role,
+    pub content: Cow<'a, str>,
+}
+impl<'a> PromptMessage<'a> {
+    pub fn new(role: Role, content: impl Into<Cow<'a, str>>) -> Self {
+        PromptMessage {
+            role,
+            content: content.into(),
+        }
+    }
+}
+
+//A prompt sent as a message of the given role, P is the prompt variant family it wraps
+pub struct RolePart<P> {
+    role: Role,
+    prompt: P,
+}
+impl<P> RolePart<P> {
+    pub fn new(role: Role, prompt: impl Into<P>) -> Self {
+        RolePart {
+            role,
+            prompt: prompt.into(),
+        }
+    }
+    pub fn system(prompt: impl Into<P>) -> Self {
+        Self::new(Role::System, prompt)
+    }
+    pub fn developer(prompt: impl Into<P>) -> Self {
+        Self::new(Role::Developer, prompt)
+    }
+    pub fn user(prompt: impl Into<P>) -> Self {
+        Self::new(Role::User, prompt)
+    }
+    pub fn assistant(prompt: impl Into<P>) -> Self {
+        Self::new(Role::Assistant, prompt)
+    }
+    pub fn get_role(&self) -> Role {
+        self.role
+    }
+    pub fn get_prompt(&self) -> &P {
+        &self.prompt
+    }
+}
+impl<C, P> Prompt<C> for RolePart<P>
+where
+    C: Context,
+    P: Prompt<C>
+{
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt.prompt_str(context)
+    }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt_messages_with(context, &HashMap::new())
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt.prompt_str_with(context, overrides)
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        Ok(self.prompt_str_with(context, overrides)?
+            .map(|content| PromptMessage::new(self.role, content))
+            .into_iter()
+            .collect())
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        self.prompt.variables()
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        self.prompt.required_variables()
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        self.prompt.missing_variables(context)
+    }
+}
+
+//Several role-tagged parts sent as one request, parts rendering to nothing are left out
+pub struct ComposedParts<P> {
+    parts: Vec<RolePart<P>>,
+}
+impl<P> Default for ComposedParts<P> {
+    fn default() -> Self {
+        ComposedParts {
+            parts: Vec::new(),
+        }
+    }
+}
+impl<P> ComposedParts<P> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    pub fn part(mut self, role: Role, prompt: impl Into<P>) -> Self {
+        self.parts.push(RolePart::new(role, prompt));
+        self
+    }
+    pub fn system(self, prompt: impl Into<P>) -> Self {
+        self.part(Role::System, prompt)
+    }
+    pub fn developer(self, prompt: impl Into<P>) -> Self {
+        self.part(Role::Developer, prompt)
+    }
+    pub fn user(self, prompt: impl Into<P>) -> Self {
+        self.part(Role::User, prompt)
+    }
+    pub fn assistant(self, prompt: impl Into<P>) -> Self {
+        self.part(Role::Assistant, prompt)
+    }
+    pub fn get_parts(&self) -> &[RolePart<P>] {
+        &self.parts
+    }
+}
+impl<P> From<RolePart<P>> for ComposedParts<P> {
+    fn from(part: RolePart<P>) -> Self {
+        ComposedParts {
+            parts: vec![part],
+        }
+    }
+}
+impl<C, P> Prompt<C> for ComposedParts<P>
+where
+    C: Context,
+    P: Prompt<C>
+{
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt_str_with(context, &HashMap::new())
+    }
+    fn prompt_messages(&self, context: &C) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.prompt_messages_with(context, &HashMap::new())
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
+        let contents = self.parts.iter()
+            .filter_map(|part| part.prompt_str_with(context, overrides).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        if contents.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Cow::Owned(contents.join("\n"))))
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        self.parts.iter()
+            .try_fold(Vec::new(), |mut acc, part| {
+                acc.extend(part.prompt_messages_with(context, overrides)?);
+                Ok(acc)
+            })
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        self.parts.iter().flat_map(|part| part.variables()).collect()
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        self.parts.iter().flat_map(|part| part.required_variables()).collect()
+    }
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        self.parts.iter().fold(Vec::new(), |mut missing, part| {
+            merge_missing(&mut missing, part.missing_variables(context));
+            missing
+        })
+    }
+}
+
+pub type RolePrompt<'a, C> = RolePart<PromptVariant<'a, C>>;
+pub type ComposedPrompt<'a, C> = ComposedParts<PromptVariant<'a, C>>;
@@ -1,95 +1,609 @@
 use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
 use crate::prompt::context::{Context};
-use crate::prompt::error::{PromptError, PromptTemplateError};
-use crate::prompt::naive::{Prompt};
+use crate::prompt::filter::{FilterRegistry, TemplateFilter};
+use crate::prompt::error::{PromptError, PromptTemplateError, TemplateErrorKind};
+use crate::prompt::naive::{merge_missing, Prompt};
+use crate::prompt::role::{PromptMessage, Role};
+use crate::prompt::value::TemplateValue;
+
+//A filter resolved at parse time, with its arguments
+#[derive(Clone)]
+pub struct FilterCall {
+    name: String,
+    args: Vec<String>,
+    filter: Arc<dyn TemplateFilter>,
+}
+impl FilterCall {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+}
+
+#[derive(Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+//A variable, the path into its value and the filters the value goes through
+#[derive(Clone)]
+pub struct Expression {
+    name: String, //as written, `docs[0].title`
+    root: String, //the variable the context is asked for, `docs`
+    path: Vec<Segment>,
+    filters: Vec<FilterCall>,
+}
+impl Expression {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+    pub fn filters(&self) -> &[FilterCall] {
+        &self.filters
+    }
+    //A filter such as `default` stands in for a missing value
+    pub fn has_fallback(&self) -> bool {
+        self.filters.iter().any(|call| call.filter.missing(&call.args).is_some())
+    }
+}
 
 pub enum TemplatePart {
     Text(String),
-    Var(String),
+    Var(Expression),
+    //`{% if expression %}then{% else %}otherwise{% endif %}`, `not` inverts the condition
+    If {
+        condition: Expression,
+        negated: bool,
+        then: Vec<TemplatePart>,
+        otherwise: Vec<TemplatePart>,
+    },
+    //`{% for item in expression %}body{% endfor %}`, see `TemplateValue::items`
+    For {
+        item: String,
+        list: Expression,
+        body: Vec<TemplatePart>,
+    },
 }
 pub struct PromptTemplate {
     parts: Vec<TemplatePart>,
 }
 impl PromptTemplate {
     pub fn new(template: &str) -> Result<Self, PromptTemplateError> {
-        #[derive(Eq, PartialEq)]
-        enum State {
-            Text,
-            WaitingBraceClose
-        }
-        let mut state = State::Text;
-
+        Self::with_filters(template, FilterRegistry::builtin())
+    }
+    //Filters are resolved against `filters`, unknown ones fail the parse
+    pub fn with_filters(template: &str, filters: &FilterRegistry) -> Result<Self, PromptTemplateError> {
+        Self::parse(template, filters).map_err(|SyntaxError { kind, at }| {
+            let help = match &kind {
+                TemplateErrorKind::UnknownFilter(_) => Some(format!("available filters: {}", filters.names().join(", "))),
+                _ => None,
+            };
+            let start = offset_in(template, at);
+            let error = PromptTemplateError::new(kind, template, start..start + at.len());
+            match help {
+                Some(help) => error.with_help(help),
+                None => error,
+            }
+        })
+    }
+    fn parse<'t>(template: &'t str, filters: &FilterRegistry) -> Result<Self, SyntaxError<'t>> {
         let mut parts = Vec::new();
-        let mut chars = template.chars().peekable();
-        let mut buffer = String::new();
-
-        while let Some(c) = chars.next() {
-            match c {
-                '{' => {
-                    if state == State::WaitingBraceClose {
-                        return Err(PromptTemplateError::BraceMismatch);
+        let mut blocks = Vec::new();
+        for token in tokenize(template)? {
+            let part = match token {
+                Token::Text(text) => TemplatePart::Text(text),
+                Token::Var(var) => TemplatePart::Var(parse_expression(&var[1..var.len() - 1], var, filters)?),
+                Token::Tag(tag) => match parse_tag(tag, filters)? {
+                    Tag::If { condition, negated } => {
+                        blocks.push((Block::If { condition, negated, then: None }, tag, Vec::new()));
+                        continue;
+                    },
+                    Tag::For { item, list } => {
+                        blocks.push((Block::For { item, list }, tag, Vec::new()));
+                        continue;
+                    },
+                    Tag::Else => match blocks.last_mut() {
+                        Some((Block::If { then: then @ None, .. }, _, parts)) => {
+                            *then = Some(std::mem::take(parts));
+                            continue;
+                        },
+                        _ => return Err(SyntaxError::new(TemplateErrorKind::UnexpectedTag("else".to_string()), tag)),
+                    },
+                    Tag::EndIf => match blocks.pop() {
+                        Some((Block::If { condition, negated, then }, _, parts)) => {
+                            let (then, otherwise) = match then {
+                                Some(then) => (then, parts),
+                                None => (parts, Vec::new()),
+                            };
+                            TemplatePart::If { condition, negated, then, otherwise }
+                        },
+                        _ => return Err(SyntaxError::new(TemplateErrorKind::UnexpectedTag("endif".to_string()), tag)),
+                    },
+                    Tag::EndFor => match blocks.pop() {
+                        Some((Block::For { item, list }, _, body)) => TemplatePart::For { item, list, body },
+                        _ => return Err(SyntaxError::new(TemplateErrorKind::UnexpectedTag("endfor".to_string()), tag)),
+                    },
+                },
+            };
+            blocks.last_mut().map_or(&mut parts, |(_, _, parts)| parts).push(part);
+        }
+        match blocks.last() {
+            None => Ok(PromptTemplate {
+                parts,
+            }),
+            Some((Block::If { .. }, tag, _)) => Err(SyntaxError::new(TemplateErrorKind::UnclosedBlock("if".to_string()), tag)),
+            Some((Block::For { .. }, tag, _)) => Err(SyntaxError::new(TemplateErrorKind::UnclosedBlock("for".to_string()), tag)),
+        }
+    }
+    fn evaluate<C: Context>(&self, expression: &Expression, context: &C, scope: &Scope<'_>) -> Result<Option<TemplateValue>, PromptError> {
+        let root = scope.get(&expression.root).or_else(|| context.template_value(&expression.root));
+        let value = root.as_ref().and_then(|root| {
+            expression.path.iter().try_fold(root, |value, segment| match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => value.index(*index),
+            })
+        });
+        if expression.filters.is_empty() {
+            return Ok(value.cloned());
+        }
+        //The first filter sees the value itself, the ones after it the text it returned
+        expression.filters.iter().try_fold(value.cloned(), |value, call| match value {
+            Some(value) => call.filter.apply_value(&value, &call.args)
+                .map(|text| Some(TemplateValue::String(text)))
+                .map_err(|message| PromptError::FilterFailed { filter: call.name.clone(), message }),
+            None => Ok(call.filter.missing(&call.args).map(TemplateValue::String)),
+        })
+    }
+    fn collect_missing<'p, C: Context>(&self, parts: &'p [TemplatePart], context: &C, scope: &mut Scope<'p>, missing: &mut Vec<String>) {
+        for part in parts {
+            match part {
+                TemplatePart::Text(_) => {},
+                TemplatePart::Var(expression) => {
+                    if let Ok(None) = self.evaluate(expression, context, scope) {
+                        merge_missing(missing, vec![expression.name.clone()]);
                     }
-                    if chars.peek() == Some(&'{') {
-                        chars.next();
-                        buffer.push('{');
-                    }else {
-                        state = State::WaitingBraceClose;
-                        parts.push(TemplatePart::Text(
-                            std::mem::take(&mut buffer)
-                        ));
+                },
+                TemplatePart::If { condition, negated, then, otherwise } => {
+                    let value = self.evaluate(condition, context, scope).ok().flatten();
+                    if value.is_some_and(|value| value.is_truthy()) != *negated {
+                        self.collect_missing(then, context, scope, missing);
+                    } else {
+                        self.collect_missing(otherwise, context, scope, missing);
                     }
-                }
-                '}' => {
-                    if chars.peek() == Some(&'}') {
-                        chars.next();
-                        buffer.push('}');
-                    }else {
-                        if state == State::WaitingBraceClose {
-                            let var_name = std::mem::take(&mut buffer);
-                            if var_name.is_empty() {
-                                return Err(PromptTemplateError::EmptyVariable);
-                            }
-                            parts.push(TemplatePart::Var(var_name));
-                        } else {
-                            return Err(PromptTemplateError::BraceMismatch);
+                },
+                TemplatePart::For { item, list, body } => match self.evaluate(list, context, scope) {
+                    Ok(None) => merge_missing(missing, vec![list.name.clone()]),
+                    Ok(Some(value)) => {
+                        for value in value.items().unwrap_or_default() {
+                            scope.locals.push((item, value));
+                            self.collect_missing(body, context, scope, missing);
+                            scope.locals.pop();
                         }
-                        state = State::Text;
+                    },
+                    Err(_) => {}, //NOTE: not a matter of missing variables
+                },
+            }
+        }
+    }
+    fn render<'p, C: Context>(&self, parts: &'p [TemplatePart], context: &C, scope: &mut Scope<'p>, rendered: &mut String) -> Result<(), PromptError> {
+        for part in parts {
+            match part {
+                TemplatePart::Text(text) => rendered.push_str(text),
+                TemplatePart::Var(expression) => match self.evaluate(expression, context, scope)? {
+                    Some(value) => {
+                        let _ = write!(rendered, "{value}");
+                    },
+                    None => return Err(PromptError::MissingContextVar(expression.name.clone())),
+                },
+                TemplatePart::If { condition, negated, then, otherwise } => {
+                    let value = self.evaluate(condition, context, scope)?;
+                    if value.is_some_and(|value| value.is_truthy()) != *negated {
+                        self.render(then, context, scope, rendered)?;
+                    } else {
+                        self.render(otherwise, context, scope, rendered)?;
                     }
-                }
-                _ => buffer.push(c)
+                },
+                TemplatePart::For { item, list, body } => {
+                    let Some(value) = self.evaluate(list, context, scope)? else {
+                        return Err(PromptError::MissingContextVar(list.name.clone()));
+                    };
+                    let items = value.items().ok_or_else(|| PromptError::NotIterable(list.name.clone()))?;
+                    for value in items {
+                        scope.locals.push((item, value));
+                        let result = self.render(body, context, scope, rendered);
+                        scope.locals.pop();
+                        result?;
+                    }
+                },
             }
         }
-        if state == State::WaitingBraceClose {
-            return Err(PromptTemplateError::BraceMismatch);
+        Ok(())
+    }
+}
+
+//Walks every branch, `required` leaves out conditions and variables with a fallback
+fn collect_variables<'p>(parts: &'p [TemplatePart], locals: &mut Vec<&'p str>, required: bool, variables: &mut BTreeSet<&'p str>) {
+    fn add<'p>(expression: &'p Expression, locals: &[&str], required: bool, variables: &mut BTreeSet<&'p str>) {
+        let optional = required && expression.has_fallback();
+        if !optional && !locals.contains(&expression.root.as_str()) {
+            variables.insert(&expression.root);
+        }
+    }
+    for part in parts {
+        match part {
+            TemplatePart::Text(_) => {},
+            TemplatePart::Var(expression) => add(expression, locals, required, variables),
+            TemplatePart::If { condition, then, otherwise, .. } => {
+                if !required {
+                    add(condition, locals, required, variables);
+                }
+                collect_variables(then, locals, required, variables);
+                collect_variables(otherwise, locals, required, variables);
+            },
+            TemplatePart::For { item, list, body } => {
+                add(list, locals, required, variables);
+                locals.push(item);
+                collect_variables(body, locals, required, variables);
+                locals.pop();
+            },
         }
-        Ok(PromptTemplate {
-            parts,
-        })
     }
 }
+
+//Variables a template is rendered with, innermost loop items first
+struct Scope<'p> {
+    overrides: &'p HashMap<String, String>,
+    locals: Vec<(&'p str, TemplateValue)>,
+}
+impl Scope<'_> {
+    fn get(&self, name: &str) -> Option<TemplateValue> {
+        self.locals.iter()
+            .rev()
+            .find(|(local, _)| *local == name)
+            .map(|(_, value)| value.clone())
+            .or_else(|| self.overrides.get(name).cloned().map(TemplateValue::String))
+    }
+}
+
+//A block still open while parsing
+enum Block {
+    If {
+        condition: Expression,
+        negated: bool,
+        then: Option<Vec<TemplatePart>>, //set once `else` is reached
+    },
+    For {
+        item: String,
+        list: Expression,
+    },
+}
+
+//A problem found while parsing, `at` is the part of the template it concerns
+struct SyntaxError<'t> {
+    kind: TemplateErrorKind,
+    at: &'t str,
+}
+impl<'t> SyntaxError<'t> {
+    fn new(kind: TemplateErrorKind, at: &'t str) -> Self {
+        SyntaxError {
+            kind,
+            at,
+        }
+    }
+}
+
+//Every slice the parser hands around borrows from the template
+fn offset_in(template: &str, part: &str) -> usize {
+    part.as_ptr() as usize - template.as_ptr() as usize
+}
+
+enum Token<'t> {
+    Text(String),
+    Var(&'t str), //with its braces
+    Tag(&'t str), //with its `{%` and `%}`
+}
+
+enum Tag {
+    If { condition: Expression, negated: bool },
+    Else,
+    EndIf,
+    For { item: String, list: Expression },
+    EndFor,
+}
+
+//Splits the template into text, `{var}` and `{% tag %}`.
+//A line holding nothing but tags is dropped with its indentation, so blocks leave no blank lines.
+fn tokenize(template: &str) -> Result<Vec<Token<'_>>, SyntaxError<'_>> {
+    #[derive(Clone, Copy)]
+    enum State {
+        Text,
+        Var { open: usize },
+        Tag { open: usize },
+        Quoted { open: usize, quote: usize, tag: bool }, //a filter argument, braces in it are plain text
+    }
+    let mut state = State::Text;
+
+    let mut tokens = Vec::new();
+    let mut chars = template.char_indices().peekable();
+    let mut buffer = String::new();
+    let mut blank_line = true; //only tags and whitespace so far on the current line
+
+    while let Some((index, c)) = chars.next() {
+        match (state, c) {
+            (State::Quoted { open, tag, .. }, c) => match c {
+                '\\' => {
+                    chars.next();
+                },
+                '"' if tag => state = State::Tag { open },
+                '"' => state = State::Var { open },
+                _ => {},
+            },
+            (State::Var { open }, '"') => state = State::Quoted { open, quote: index, tag: false },
+            (State::Tag { open }, '"') => state = State::Quoted { open, quote: index, tag: true },
+            (State::Var { open }, '}') => {
+                tokens.push(Token::Var(&template[open..=index]));
+                blank_line = false;
+                state = State::Text;
+            },
+            (State::Var { .. }, '{') => return Err(SyntaxError::new(TemplateErrorKind::NestedBrace, &template[index..=index])),
+            (State::Var { open }, '\n') => return Err(SyntaxError::new(TemplateErrorKind::UnclosedBrace, &template[open..=open])),
+            (State::Var { .. }, _) => {},
+            (State::Tag { open }, '%') if chars.next_if(|&(_, c)| c == '}').is_some() => {
+                tokens.push(Token::Tag(&template[open..index + 2]));
+                state = State::Text;
+                if blank_line && matches!(chars.peek(), Some((_, '\n')) | None) {
+                    chars.next();
+                    let text = tokens.iter_mut().rev().find_map(|token| match token {
+                        Token::Tag(_) => None,
+                        token => Some(token),
+                    });
+                    if let Some(Token::Text(text)) = text {
+                        text.truncate(text.trim_end_matches([' ', '\t']).len());
+                    }
+                }
+            },
+            (State::Tag { .. }, _) => {},
+            (State::Text, '{') => {
+                if chars.next_if(|&(_, c)| c == '{').is_some() {
+                    buffer.push('{');
+                    blank_line = false;
+                } else {
+                    state = if chars.next_if(|&(_, c)| c == '%').is_some() {
+                        State::Tag { open: index }
+                    } else {
+                        State::Var { open: index }
+                    };
+                    if !buffer.is_empty() {
+                        tokens.push(Token::Text(std::mem::take(&mut buffer)));
+                    }
+                }
+            },
+            (State::Text, '}') => {
+                if chars.next_if(|&(_, c)| c == '}').is_none() {
+                    return Err(SyntaxError::new(TemplateErrorKind::StrayBrace, &template[index..=index]));
+                }
+                buffer.push('}');
+                blank_line = false;
+            },
+            (State::Text, c) => {
+                blank_line = c == '\n' || (blank_line && c.is_whitespace());
+                buffer.push(c);
+            },
+        }
+    }
+    match state {
+        State::Text => {},
+        State::Var { open } => return Err(SyntaxError::new(TemplateErrorKind::UnclosedBrace, &template[open..=open])),
+        State::Tag { open } => return Err(SyntaxError::new(TemplateErrorKind::UnterminatedTag, &template[open..open + 2])),
+        State::Quoted { quote, .. } => return Err(SyntaxError::new(TemplateErrorKind::UnterminatedString, &template[quote..=quote])),
+    }
+    if !buffer.is_empty() {
+        tokens.push(Token::Text(buffer));
+    }
+    Ok(tokens)
+}
+
+fn parse_tag<'t>(whole: &'t str, filters: &FilterRegistry) -> Result<Tag, SyntaxError<'t>> {
+    let tag = whole[2..whole.len() - 2].trim();
+    let (keyword, rest) = tag.split_once(char::is_whitespace)
+        .map_or((tag, ""), |(keyword, rest)| (keyword, rest.trim_start()));
+    let invalid = || SyntaxError::new(TemplateErrorKind::InvalidTag(tag.to_string()), whole);
+    match keyword {
+        "if" => {
+            let (negated, condition) = match rest.strip_prefix("not").filter(|condition| condition.starts_with(char::is_whitespace)) {
+                Some(condition) => (true, condition),
+                None => (false, rest),
+            };
+            if condition.trim().is_empty() {
+                return Err(invalid());
+            }
+            Ok(Tag::If {
+                condition: parse_expression(condition, whole, filters)?,
+                negated,
+            })
+        },
+        "for" => {
+            let (item, list) = rest.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let list = list.trim_start()
+                .strip_prefix("in")
+                .filter(|list| list.starts_with(char::is_whitespace) && !list.trim().is_empty())
+                .ok_or_else(invalid)?;
+            if !is_identifier(item) {
+                return Err(SyntaxError::new(TemplateErrorKind::InvalidIdentifier(item.to_string()), item));
+            }
+            Ok(Tag::For {
+                item: item.to_string(),
+                list: parse_expression(list, whole, filters)?,
+            })
+        },
+        "" => Err(invalid()),
+        "else" | "endif" | "endfor" if !rest.is_empty() => Err(invalid()),
+        "else" => Ok(Tag::Else),
+        "endif" => Ok(Tag::EndIf),
+        "endfor" => Ok(Tag::EndFor),
+        _ => Err(SyntaxError::new(TemplateErrorKind::UnknownTag(keyword.to_string()), keyword)),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
 impl<C: Context> Prompt<C> for PromptTemplate {
-    fn prompt_str(&self, context: &C) -> Result<Option<Cow<str>>, PromptError> {
+    fn prompt_str(&self, context: &C) -> Result<Option<Cow<'_, str>>, PromptError> {
+        self.prompt_str_with(context, &HashMap::new())
+    }
+    fn prompt_messages_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Vec<PromptMessage<'_>>, PromptError> {
+        Ok(self.prompt_str_with(context, overrides)?
+            .map(|content| PromptMessage::new(Role::User, content))
+            .into_iter()
+            .collect())
+    }
+    fn prompt_str_with(&self, context: &C, overrides: &HashMap<String, String>) -> Result<Option<Cow<'_, str>>, PromptError> {
         if self.parts.is_empty() {
             return Ok(None);
         }
         if self.parts.len() == 1 && let TemplatePart::Text(text) = &self.parts[0] {
             return Ok(Some(Cow::Borrowed(text)));
         }
-        let rendered_str = self.parts.iter()
-            .try_fold(String::new(), |mut acc, part| {
-                match part {
-                    TemplatePart::Text(text) => acc.push_str(text),
-                    TemplatePart::Var(var_name) => {
-                        if let Some(var_value) = context.template_var(var_name) {
-                            acc.push_str(&var_value);
-                        } else {
-                            return Err(PromptError::MissingContextVar(var_name.clone()));
-                        }
-                    }
+        let mut scope = Scope {
+            overrides,
+            locals: Vec::new(),
+        };
+        let mut rendered = String::new();
+        self.render(&self.parts, context, &mut scope, &mut rendered)?;
+        Ok(Some(Cow::Owned(rendered)))
+    }
+    fn variables(&self) -> BTreeSet<&str> {
+        let mut variables = BTreeSet::new();
+        collect_variables(&self.parts, &mut Vec::new(), false, &mut variables);
+        variables
+    }
+    fn required_variables(&self) -> BTreeSet<&str> {
+        let mut variables = BTreeSet::new();
+        collect_variables(&self.parts, &mut Vec::new(), true, &mut variables);
+        variables
+    }
+    //Follows the branches and loops the context leads to, as rendering would
+    fn missing_variables(&self, context: &C) -> Vec<String> {
+        let overrides = HashMap::new();
+        let mut scope = Scope {
+            overrides: &overrides,
+            locals: Vec::new(),
+        };
+        let mut missing = Vec::new();
+        self.collect_missing(&self.parts, context, &mut scope, &mut missing);
+        missing
+    }
+}
+
+//Splits on `separator` where it is not quoted
+fn split_unquoted(input: &str, separator: char) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                pieces.push(&input[start..index]);
+                start = index + c.len_utf8();
+            },
+            _ => {},
+        }
+    }
+    pieces.push(&input[start..]);
+    pieces
+}
+
+//Quoted arguments keep their spaces and may escape `"` and `\\`, bare ones are trimmed
+fn parse_arg(arg: &str) -> String {
+    let arg = arg.trim();
+    match arg.strip_prefix('"').and_then(|arg| arg.strip_suffix('"')) {
+        Some(quoted) => {
+            let mut unescaped = String::with_capacity(quoted.len());
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unescaped.extend(chars.next()),
+                    c => unescaped.push(c),
                 }
-                Ok(acc)
+            }
+            unescaped
+        },
+        None => arg.to_string(),
+    }
+}
+
+//`name|filter|filter:arg,"quoted arg"`, `whole` is the variable or tag it comes from
+fn parse_expression<'t>(var: &'t str, whole: &'t str, filters: &FilterRegistry) -> Result<Expression, SyntaxError<'t>> {
+    let mut pieces = split_unquoted(var, '|').into_iter();
+    let name = pieces.next().unwrap_or_default().trim();
+    if name.is_empty() {
+        return Err(SyntaxError::new(TemplateErrorKind::EmptyVariable, whole));
+    }
+    let calls = pieces
+        .map(|piece| {
+            let (filter_name, args, arg_span) = match piece.split_once(':') {
+                Some((filter_name, args)) => (filter_name.trim(), split_unquoted(args, ',').into_iter().map(parse_arg).collect(), args.trim()),
+                None => (piece.trim(), Vec::new(), piece.trim()),
+            };
+            if filter_name.is_empty() {
+                return Err(SyntaxError::new(TemplateErrorKind::EmptyFilter(name.to_string()), piece));
+            }
+            let filter = filters.get(filter_name)
+                .ok_or_else(|| SyntaxError::new(TemplateErrorKind::UnknownFilter(filter_name.to_string()), filter_name))?;
+            filter.check(&args).map_err(|message| SyntaxError::new(TemplateErrorKind::InvalidFilterArgs {
+                filter: filter_name.to_string(),
+                message,
+            }, arg_span))?;
+            Ok(FilterCall {
+                name: filter_name.to_string(),
+                args,
+                filter: filter.clone(),
             })
-            .map(Cow::Owned)?;
-        Ok(Some(rendered_str))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut path = parse_path(name)?;
+    let Segment::Key(root) = path.remove(0) else {
+        unreachable!("paths start with a key");
+    };
+    Ok(Expression {
+        name: name.to_string(),
+        root,
+        path,
+        filters: calls,
+    })
+}
+
+//`user.address.city`, `docs[0].title`, the first segment is always a key
+fn parse_path(name: &str) -> Result<Vec<Segment>, SyntaxError<'_>> {
+    let mut path = Vec::new();
+    for piece in name.split('.') {
+        let (key, mut indices) = piece.split_at(piece.find('[').unwrap_or(piece.len()));
+        if !is_identifier(key) {
+            return Err(SyntaxError::new(TemplateErrorKind::InvalidIdentifier(key.to_string()), key));
+        }
+        path.push(Segment::Key(key.to_string()));
+        while !indices.is_empty() {
+            let invalid = || SyntaxError::new(TemplateErrorKind::InvalidPath(name.to_string()), indices);
+            let (index, rest) = indices.strip_prefix('[')
+                .and_then(|indices| indices.split_once(']'))
+                .ok_or_else(invalid)?;
+            path.push(Segment::Index(index.trim().parse().map_err(|_| invalid())?));
+            indices = rest;
+        }
     }
-}
\ No newline at end of file
+    Ok(path)
+}
@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+//A value a context hands to templates, reached into with paths like `{docs[0].title}`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateValue {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    List(Vec<TemplateValue>),
+    Map(BTreeMap<String, TemplateValue>),
+}
+impl TemplateValue {
+    pub fn map<K, V>(entries: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<TemplateValue>
+    {
+        TemplateValue::Map(entries.into_iter().map(|(key, value)| (key.into(), value.into())).collect())
+    }
+    pub fn get(&self, key: &str) -> Option<&TemplateValue> {
+        match self {
+            TemplateValue::Map(map) => map.get(key),
+            TemplateValue::List(list) => key.parse::<usize>().ok().and_then(|index| list.get(index)),
+            _ => None,
+        }
+    }
+    pub fn index(&self, index: usize) -> Option<&TemplateValue> {
+        match self {
+            TemplateValue::List(list) => list.get(index),
+            _ => None,
+        }
+    }
+    //Empty values, `false` and zero are false. Strings read `false` and `0` as false too,
+    //for contexts that hand everything over as text.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            TemplateValue::String(value) => !matches!(value.as_str(), "" | "false" | "0"),
+            TemplateValue::Number(value) => *value != 0.0,
+            TemplateValue::Bool(value) => *value,
+            TemplateValue::List(list) => !list.is_empty(),
+            TemplateValue::Map(map) => !map.is_empty(),
+        }
+    }
+    //What a `for` block runs over: the items of a list, the lines of a string,
+    //or the entries of a map as `key` and `value`
+    pub fn items(&self) -> Option<Vec<TemplateValue>> {
+        match self {
+            TemplateValue::List(list) => Some(list.clone()),
+            TemplateValue::String(value) => Some(value.lines().map(TemplateValue::from).collect()),
+            TemplateValue::Map(map) => Some(map.iter()
+                .map(|(key, value)| TemplateValue::map([("key", TemplateValue::from(key.as_str())), ("value", value.clone())]))
+                .collect()),
+            _ => None,
+        }
+    }
+}
+//Lists put an item on each line, maps an entry, as `key: value`
+impl fmt::Display for TemplateValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateValue::String(value) => f.write_str(value),
+            TemplateValue::Number(value) => write!(f, "{value}"),
+            TemplateValue::Bool(value) => write!(f, "{value}"),
+            TemplateValue::List(list) => {
+                for (index, item) in list.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str("\n")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                Ok(())
+            },
+            TemplateValue::Map(map) => {
+                for (index, (key, value)) in map.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str("\n")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+impl From<String> for TemplateValue {
+    fn from(value: String) -> Self {
+        TemplateValue::String(value)
+    }
+}
+impl From<&str> for TemplateValue {
+    fn from(value: &str) -> Self {
+        TemplateValue::String(value.to_string())
+    }
+}
+impl From<bool> for TemplateValue {
+    fn from(value: bool) -> Self {
+        TemplateValue::Bool(value)
+    }
+}
+macro_rules! number_from {
+    ($($number:ty),*) => {
+        $(impl From<$number> for TemplateValue {
+            fn from(value: $number) -> Self {
+                TemplateValue::Number(value as f64)
+            }
+        })*
+    };
+}
+number_from!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+impl<T: Into<TemplateValue>> From<Vec<T>> for TemplateValue {
+    fn from(list: Vec<T>) -> Self {
+        TemplateValue::List(list.into_iter().map(Into::into).collect())
+    }
+}
+impl<K: Into<String>, V: Into<TemplateValue>> From<HashMap<K, V>> for TemplateValue {
+    fn from(map: HashMap<K, V>) -> Self {
+        TemplateValue::map(map)
+    }
+}
+impl<K: Into<String>, V: Into<TemplateValue>> From<BTreeMap<K, V>> for TemplateValue {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        TemplateValue::map(map)
+    }
+}
+//`null` becomes an empty string, so it reads as false in conditions
+#[cfg(feature = "executable")]
+impl From<serde_json::Value> for TemplateValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => TemplateValue::String(String::new()),
+            serde_json::Value::Bool(value) => TemplateValue::Bool(value),
+            serde_json::Value::Number(value) => TemplateValue::Number(value.as_f64().unwrap_or_default()),
+            serde_json::Value::String(value) => TemplateValue::String(value),
+            serde_json::Value::Array(list) => list.into(),
+            serde_json::Value::Object(map) => TemplateValue::map(map),
+        }
+    }
+}